@@ -2,5 +2,6 @@
 
 //! Library for the minecraft resource tree app.
 
+pub mod optimizer;
 pub mod recipes;
-pub mod ui;
\ No newline at end of file
+pub mod ui;
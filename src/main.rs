@@ -1,28 +1,52 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
     env::current_dir,
     fmt::Display,
     fs::File,
+    ops::Deref,
     path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
-use craft_tree_optimizer::ui::{
-    DisplayFloat, GRAY, Item, ParseTargetAmountError, SPACE, TargetAmount, TitleLevel, contoured,
-    recipe::{self, BuilderState, EditableContentSave},
-    title_text,
+use craft_tree_optimizer::{
+    optimizer::{
+        self, Costs, Goal, ItemRole, Model, ModelRecipe, ParetoMethod, Plan, SolveError, WhyNot,
+        attribution::Share,
+        export::ModelFormat,
+        rule::{ParseRuleError, Rule},
+    },
+    recipes::Recipe,
+    ui::{
+        DisplayFloat, GRAY, Item, ParseTargetAmountError, SPACE, TargetAmount, TitleLevel,
+        contoured, danger_if,
+        recipe::{
+            self, BuilderState, EditableContentSave, ZERO_COST,
+            helpers::{EMPTY_ITEM, ParseQuantityError, Quantity},
+        },
+        title_text,
+    },
 };
-use good_lp::{Expression, ProblemVariables, Solution, SolverModel, solvers, variable};
 use iced::{
     Element,
     Length::*,
-    Padding, Subscription, Task, keyboard,
+    Padding, Subscription, Task,
+    futures::{
+        Stream,
+        channel::{mpsc, oneshot},
+    },
+    keyboard,
     widget::{
         self, Checkbox, Column, Container, Scrollable, Space, Stack, button, center, column,
-        container, horizontal_rule, horizontal_space, opaque, row, text, text_input,
+        container, horizontal_rule, horizontal_space, opaque, pick_list, row, text, text_input,
     },
     window,
 };
-use iced_aw::{ContextMenu, TypedInput};
+use iced_aw::{ContextMenu, Spinner, TypedInput};
 use more_iced_aw::{
     element_vec,
     grid::{self, Grid},
@@ -31,10 +55,16 @@ use more_iced_aw::{
 use rfd::FileDialog;
 use serde::{Deserialize, Serialize};
 
-const EXTENSION: &'static str = "crtr";
+const EXTENSION: &str = "crtr";
+
+/// Time without edits before a live project is computed again.
+const LIVE_DELAY: Duration = Duration::from_millis(500);
+/// Time between two checks of [`LIVE_DELAY`] while it runs.
+const LIVE_TICK: Duration = Duration::from_millis(100);
 
 struct App {
     recipes: Vec<recipe::EditableContent>,
+    #[allow(clippy::type_complexity)]
     known_items: BTreeMap<
         Item,
         (
@@ -43,10 +73,58 @@ struct App {
             Option<parsed_input::Content<TargetAmount, ParseTargetAmountError>>,
         ),
     >, // Quantity, target, raw
+    buy_prices: BTreeMap<Item, parsed_input::Content<TargetAmount, ParseTargetAmountError>>, // Price of the items that can be bought
+    buy_dimension_prices: BTreeMap<
+        Item,
+        BTreeMap<usize, parsed_input::Content<TargetAmount, ParseTargetAmountError>>,
+    >, // Price of the items that can be bought, by dimension id
+    target_priorities: BTreeMap<Item, u32>, // Priority level of the targets, when maximizing the output. Missing is 0.
+    fuels: BTreeMap<Item, parsed_input::Content<TargetAmount, ParseTargetAmountError>>, // Smelts per item
+    tags: BTreeMap<Item, Vec<Item>>,  // Members of each tag
+    new_tag: Item,                    // Name of the next tag added. Not saved.
+    tag_drafts: BTreeMap<Item, Item>, // Member being typed in for each tag. Not saved.
+    modifiers: Vec<Modifier<parsed_input::Content<TargetAmount, ParseTargetAmountError>>>,
+    ratio_groups: Vec<
+        RatioGroup<
+            parsed_input::Content<TargetAmount, ParseTargetAmountError>,
+            parsed_input::Content<Quantity, ParseQuantityError>,
+        >,
+    >,
+    cost_weight: parsed_input::Content<TargetAmount, ParseTargetAmountError>, // Weight of the main cost dimension
+    dimensions: Vec<CostDimension<parsed_input::Content<TargetAmount, ParseTargetAmountError>>>,
+    next_dimension_id: usize,
+    raw_dimension_costs: BTreeMap<
+        Item,
+        BTreeMap<usize, parsed_input::Content<TargetAmount, ParseTargetAmountError>>,
+    >, // Cost of one raw item, by dimension id
+    raw_limits: BTreeMap<Item, parsed_input::Content<TargetAmount, ParseTargetAmountError>>, // Available amount of raw items
+    max_production: BTreeMap<Item, parsed_input::Content<TargetAmount, ParseTargetAmountError>>, // Maximum amount produced, bought included
+    max_surplus: BTreeMap<Item, parsed_input::Content<TargetAmount, ParseTargetAmountError>>, // Maximum net production
+    goal: Goal,
+    rules: Vec<(String, Result<Rule, ParseRuleError>)>, // Linear constraints written by the user, parsed on edit
+    uses_locks: Vec<Option<UsesLock<parsed_input::Content<TargetAmount, ParseTargetAmountError>>>>, // Lock of the uses of each recipe, by index. May be shorter than the recipes.
+    max_recipes: Option<usize>, // Maximum number of different recipes used
+    recipe_penalty: Option<parsed_input::Content<TargetAmount, ParseTargetAmountError>>, // Cost of each different recipe used
+    pareto_axes: (Option<usize>, Option<usize>), // Cost dimension ids of the Pareto frontier, None for the main one
+    pareto_method: ParetoMethod,
+    pareto_steps: usize,
+    pareto: Option<(Vec<Plan>, Option<usize>)>, // Pareto frontier, and the loaded point
+    price_list: Option<BTreeMap<Item, Result<Costs, String>>>, // Cost of one of each item
+    why_not: Option<(usize, Result<WhyNot, String>)>, // Why a recipe is not used, by recipe index
     error: Option<String>,
+    solving: Option<Worker>, // Computation running on a worker thread, until the thread finishes
+    generation: u64,         // Number of edits, to recognize the results of an outdated model
+    live: bool,              // Compute again after each edit
+    live_deadline: Option<Instant>, // When to compute again in live mode, if no other edit comes. Not saved.
 
     recipe_uses: Option<Vec<f64>>,
     item_stats: Option<HashMap<Item, (f64, f64)>>, // produced used
+    bought: Option<HashMap<Item, f64>>,
+    burnt: Option<HashMap<Item, f64>>,
+    tag_uses: Option<HashMap<Item, Vec<(Item, f64)>>>, // Amount of each member used as each tag
+    shares: Option<BTreeMap<Item, Share>>, // Share of each target and ratio group member in the raw materials and costs. Computed again on load.
+    expanded_shares: BTreeSet<Item>,       // Targets and members whose share is shown
+    pruned: Option<(usize, usize)>, // Recipes and items left out of the last optimization. Not saved.
     scale: TargetAmount,
 
     unsaved_changes: bool,
@@ -57,6 +135,135 @@ struct App {
     save_popup: Option<Message>,
 }
 
+/// A computation running on a worker thread.
+struct Worker {
+    generation: u64,         // Generation of the model it computes
+    cancel: Arc<AtomicBool>, // Set to stop it before its next optimization, if it runs several
+}
+
+/// Scales the quantities of the recipes with a given label.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Modifier<A> {
+    name: String,
+    label: String, // Label of the recipes it applies to. Empty for all recipes.
+    enabled: bool,
+    products: A,    // Multiplier of the quantities produced
+    ingredients: A, // Multiplier of the quantities consumed
+}
+
+impl<A> Modifier<A> {
+    fn applies_to<T>(&self, recipe: &Recipe<T>) -> bool {
+        self.enabled && (self.label.is_empty() || recipe.get_labels().contains(&self.label))
+    }
+}
+
+#[derive(Debug, Clone)]
+enum ModifierEdit {
+    Toggle(bool),
+    Name(String),
+    Label(String),
+    Products(parsed_input::Parsed<TargetAmount, ParseTargetAmountError>),
+    Ingredients(parsed_input::Parsed<TargetAmount, ParseTargetAmountError>),
+    Delete,
+}
+
+/// Target items produced together in a fixed ratio, by complete sets.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RatioGroup<A, R> {
+    name: String,
+    amount: A, // Sets to produce, or weight of one set when maximizing the output
+    members: Vec<(Item, R)>, // Item, number per set
+    #[serde(default)]
+    priority: u32, // Priority level, when maximizing the output
+}
+
+#[derive(Debug, Clone)]
+enum TagEdit {
+    Draft(Item),
+    AddMember, // Adds the draft
+    RemoveMember(usize),
+    Delete,
+}
+
+#[derive(Debug, Clone)]
+enum RatioGroupEdit {
+    Name(String),
+    Amount(parsed_input::Parsed<TargetAmount, ParseTargetAmountError>),
+    Member(usize, Item), // An empty member deletes the line.
+    Ratio(usize, parsed_input::Parsed<Quantity, ParseQuantityError>),
+    Priority(u32),
+    Delete,
+}
+
+/// Bounds on the uses of a recipe, set by the user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UsesLock<A> {
+    range: bool, // Between min and max, instead of fixed to min
+    min: A,
+    max: A,
+}
+
+#[derive(Debug, Clone)]
+enum UsesLockEdit {
+    Range(bool),
+    Min(parsed_input::Parsed<TargetAmount, ParseTargetAmountError>),
+    Max(parsed_input::Parsed<TargetAmount, ParseTargetAmountError>),
+}
+
+/// A cost dimension other than the main one, weighted in the objective.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CostDimension<A> {
+    id: usize,
+    name: String,
+    weight: A,
+}
+
+#[derive(Debug, Clone)]
+enum DimensionEdit {
+    Name(String),
+    Weight(parsed_input::Parsed<TargetAmount, ParseTargetAmountError>),
+    Delete,
+}
+
+/// Parts of the project that are ready to be computed.
+#[derive(Clone, Copy)]
+struct Checks {
+    recipes: bool,
+    targets: bool,
+    raws: bool,
+    supplies: bool, // Fuels, buy prices, and production and surplus limits
+    modifiers: bool,
+    dimensions: bool,
+    rules: bool,
+    locks: bool,
+}
+
+impl Checks {
+    fn all(&self) -> bool {
+        self.recipes
+            && self.targets
+            && self.raws
+            && self.supplies
+            && self.modifiers
+            && self.dimensions
+            && self.rules
+            && self.locks
+    }
+}
+
+/// A cost dimension in a pick list. The main one has no id.
+#[derive(Debug, Clone, PartialEq)]
+struct AxisChoice {
+    id: Option<usize>,
+    name: String,
+}
+
+impl Display for AxisChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
 #[derive(Debug, Clone)]
 enum Message {
     Action(usize, recipe::EditableAction),
@@ -64,8 +271,14 @@ enum Message {
     Edit(usize),
     Delete(usize),
     AddRecipe,
+    ToggleUsesLock(usize, bool),
+    WhyNot(usize),
+    WhyNotComputed(u64, usize, Result<WhyNot, String>), // Generation of the model, recipe index, explanation
+    CloseWhyNot,
+    EditUsesLock(usize, UsesLockEdit),
 
     ToggleTarget(Item, bool),
+    EditTargetPriority(Item, u32),
     EditTargetAmount(
         Item,
         parsed_input::Parsed<TargetAmount, ParseTargetAmountError>,
@@ -75,9 +288,80 @@ enum Message {
         Item,
         parsed_input::Parsed<TargetAmount, ParseTargetAmountError>,
     ),
+    ToggleRawLimit(Item, bool),
+    EditRawLimit(
+        Item,
+        parsed_input::Parsed<TargetAmount, ParseTargetAmountError>,
+    ),
+    ToggleMaxProduction(Item, bool),
+    EditMaxProduction(
+        Item,
+        parsed_input::Parsed<TargetAmount, ParseTargetAmountError>,
+    ),
+    ToggleMaxSurplus(Item, bool),
+    EditMaxSurplus(
+        Item,
+        parsed_input::Parsed<TargetAmount, ParseTargetAmountError>,
+    ),
+    ToggleMaximize(bool),
+    ToggleShare(Item, bool),
+    AddRule,
+    EditRule(usize, String),
+    DeleteRule(usize),
+    ToggleMaxRecipes(bool),
+    EditMaxRecipes(usize),
+    ToggleRecipePenalty(bool),
+    EditRecipePenalty(parsed_input::Parsed<TargetAmount, ParseTargetAmountError>),
+    ToggleFuel(Item, bool),
+    ToggleBuy(Item, bool),
+    EditBuyPrice(
+        Item,
+        parsed_input::Parsed<TargetAmount, ParseTargetAmountError>,
+    ),
+    EditBuyDimensionPrice(
+        Item,
+        usize,
+        parsed_input::Parsed<TargetAmount, ParseTargetAmountError>,
+    ),
+    EditNewTag(Item),
+    AddTag,
+    EditTag(Item, TagEdit),
+    AddModifier,
+    EditModifier(usize, ModifierEdit),
+    AddRatioGroup,
+    EditRatioGroup(usize, RatioGroupEdit),
+    EditCostWeight(parsed_input::Parsed<TargetAmount, ParseTargetAmountError>),
+    AddDimension,
+    EditDimension(usize, DimensionEdit),
+    EditRawDimensionCost(
+        Item,
+        usize,
+        parsed_input::Parsed<TargetAmount, ParseTargetAmountError>,
+    ),
+    EditFuelSmelts(
+        Item,
+        parsed_input::Parsed<TargetAmount, ParseTargetAmountError>,
+    ),
 
     Compute,
+    Solved(u64, Result<Plan, SolveError>), // Generation of the model, result
+    CancelCompute,
+    ToggleLive(bool),
+    AutoCompute(u64), // Generation of the model when the edit was made
+    LiveTick,
+    WorkerStopped(u64), // Generation of the model. The worker thread panicked.
     ComputeError(String),
+    ExportModel,
+
+    SetParetoAxis(bool, Option<usize>), // Y axis, dimension id
+    ToggleParetoEpsilon(bool),
+    EditParetoSteps(usize),
+    ComputePareto,
+    ParetoComputed(u64, Result<Vec<Plan>, SolveError>), // Generation of the model, frontier
+    LoadParetoPoint(usize),
+    ComputePriceList,
+    PriceListComputed(u64, BTreeMap<Item, Result<Costs, String>>), // Generation of the model, price list
+    ExportPriceList,
 
     EditScale(TargetAmount),
 
@@ -98,31 +382,6 @@ enum Message {
     PopupCancel,
 }
 
-macro_rules! remove_recipe_items {
-    ($app:expr, $recipe:expr) => {
-        match $recipe {
-            recipe::EditableContent::Builder(_) => (),
-            recipe::EditableContent::Built(recipe) => {
-                let inputs = recipe.get_ingredients().iter().map(|(item, _)| item);
-                let outputs = recipe.get_products().iter().map(|(item, _, _)| item);
-
-                for item in inputs.chain(outputs) {
-                    match $app.known_items.get_mut(item) {
-                        Some((qty, _, _)) => {
-                            if *qty == 0 {
-                                $app.known_items.remove(item);
-                            } else {
-                                *qty -= 1
-                            }
-                        }
-                        None => (),
-                    }
-                }
-            }
-        }
-    };
-}
-
 impl App {
     fn update(&mut self, message: Message) -> Task<Message> {
         self.import_error = None;
@@ -133,195 +392,662 @@ impl App {
                 .get_mut(index)
                 .map(|reicpe| reicpe.perform(editable_action))
                 .unwrap_or_default(),
-            Message::Build(index) => self
-                .recipes
-                .get_mut(index)
-                .map(|recipe| {
+            Message::Build(index) => {
+                if let Some(recipe) = self.recipes.get_mut(index) {
                     recipe.perform(recipe::EditableAction::Build);
-                    match recipe {
-                        recipe::EditableContent::Builder(_) => (),
-                        recipe::EditableContent::Built(recipe) => {
-                            let inputs = recipe.get_ingredients().iter().map(|(item, _)| item);
-                            let outputs = recipe.get_products().iter().map(|(item, _, _)| item);
-
-                            for item in inputs.chain(outputs) {
-                                match self.known_items.get_mut(item) {
-                                    Some((qty, _, _)) => *qty += 1,
-                                    None => {
-                                        self.known_items.insert(item.clone(), (0, None, None));
-                                    }
-                                }
-                            }
-                        }
-                    }
-                })
-                .unwrap_or_default(),
-            Message::Edit(index) => self
-                .recipes
-                .get_mut(index)
-                .map(|recipe| {
-                    remove_recipe_items!(self, recipe);
+                }
+                for item in self.recipe_items(index) {
+                    self.add_known_item(&item);
+                }
+            }
+            Message::Edit(index) => {
+                for item in self.recipe_items(index) {
+                    self.remove_known_item(&item);
+                }
+                if let Some(recipe) = self.recipes.get_mut(index) {
                     recipe.perform(recipe::EditableAction::Edit);
-                })
-                .unwrap_or_default(),
+                }
+            }
             Message::Delete(index) => {
-                let recipes = &mut self.recipes;
-                recipes
-                    .get_mut(index)
-                    .map(|recipe| remove_recipe_items!(self, recipe));
-                if index < recipes.len() {
-                    recipes.remove(index);
+                for item in self.recipe_items(index) {
+                    self.remove_known_item(&item);
+                }
+                if index < self.recipes.len() {
+                    self.recipes.remove(index);
+                }
+                if index < self.uses_locks.len() {
+                    self.uses_locks.remove(index);
+                }
+                if let Some(uses) = self.recipe_uses.as_mut().filter(|uses| index < uses.len()) {
+                    uses.remove(index);
                 }
             }
             Message::AddRecipe => {
                 let content = BuilderState::new();
                 self.recipes.push(recipe::EditableContent::Builder(content));
             }
+            Message::ToggleUsesLock(index, toggle) => {
+                if self.uses_locks.len() <= index {
+                    self.uses_locks.resize(index + 1, None);
+                }
+                self.uses_locks[index] = toggle.then(|| {
+                    // Start from the current uses, if known.
+                    let uses = self
+                        .recipe_uses
+                        .as_ref()
+                        .and_then(|uses| uses.get(index))
+                        .and_then(|nb| TargetAmount::try_from(*nb).ok())
+                        .unwrap_or_default();
+                    UsesLock {
+                        range: false,
+                        min: parsed_input::Content::new(uses),
+                        max: parsed_input::Content::new(uses),
+                    }
+                });
+            }
+            Message::EditUsesLock(index, edit) => {
+                if let Some(Some(lock)) = self.uses_locks.get_mut(index) {
+                    match edit {
+                        UsesLockEdit::Range(range) => lock.range = range,
+                        UsesLockEdit::Min(val) => lock.min.update(val),
+                        UsesLockEdit::Max(val) => lock.max.update(val),
+                    }
+                }
+            }
             Message::ToggleTarget(item, toggle) => {
-                self.known_items.get_mut(&item).map(|(_, target, _)| {
+                if let Some((_, target, _)) = self.known_items.get_mut(&item) {
                     if toggle {
                         target.get_or_insert(parsed_input::Content::default());
                     } else {
                         *target = None
                     }
-                });
+                }
+                if !toggle {
+                    self.target_priorities.remove(&item);
+                }
+            }
+            Message::EditTargetPriority(item, priority) => {
+                self.target_priorities.insert(item, priority);
             }
             Message::EditTargetAmount(item, val) => {
-                self.known_items
+                if let Some(c) = self
+                    .known_items
                     .get_mut(&item)
                     .and_then(|(_, c, _)| c.as_mut())
-                    .map(|c| c.update(val));
+                {
+                    c.update(val)
+                }
             }
             Message::ToggleRaw(item, toggle) => {
-                self.known_items.get_mut(&item).map(|(_, _, raw)| {
+                if let Some((_, _, raw)) = self.known_items.get_mut(&item) {
                     if toggle {
                         raw.get_or_insert(parsed_input::Content::default());
                     } else {
                         *raw = None
                     }
-                });
+                }
+                if !toggle {
+                    self.raw_dimension_costs.remove(&item);
+                    self.raw_limits.remove(&item);
+                }
             }
             Message::EditRawCost(item, val) => {
-                self.known_items
+                if let Some(c) = self
+                    .known_items
                     .get_mut(&item)
                     .and_then(|(_, _, c)| c.as_mut())
-                    .map(|c| c.update(val));
+                {
+                    c.update(val)
+                }
             }
-            Message::Compute => {
-                // One variable per recipes, all superior to 0.
-                let mut problem = ProblemVariables::new();
-                let variables = problem.add_vector(variable().min(0), self.recipes.len());
-
-                // Item expressions. Hash map with (expr_prod expr_uses)
-                let mut item_expressions: HashMap<Item, (Expression, Expression)> = self
-                    .known_items
-                    .iter()
-                    .map(|(item, _)| (item.clone(), Default::default()))
-                    .collect();
-
-                // For each recipe, edit the expressions of the items.
-                for (index, recipe) in self.recipes.iter().enumerate() {
-                    match recipe {
-                        recipe::EditableContent::Builder(_) => {
-                            return Task::done(Message::ComputeError(
-                                "One of the recipies is not build.".to_string(),
-                            ));
+            Message::ToggleRawLimit(item, toggle) => {
+                if toggle {
+                    self.raw_limits.entry(item).or_default();
+                } else {
+                    self.raw_limits.remove(&item);
+                }
+            }
+            Message::EditRawLimit(item, val) => {
+                if let Some(c) = self.raw_limits.get_mut(&item) {
+                    c.update(val)
+                }
+            }
+            Message::ToggleMaxProduction(item, toggle) => {
+                if toggle {
+                    self.max_production.entry(item).or_default();
+                } else {
+                    self.max_production.remove(&item);
+                }
+            }
+            Message::EditMaxProduction(item, val) => {
+                if let Some(c) = self.max_production.get_mut(&item) {
+                    c.update(val)
+                }
+            }
+            Message::ToggleMaxSurplus(item, toggle) => {
+                if toggle {
+                    self.max_surplus.entry(item).or_default();
+                } else {
+                    self.max_surplus.remove(&item);
+                }
+            }
+            Message::EditMaxSurplus(item, val) => {
+                if let Some(c) = self.max_surplus.get_mut(&item) {
+                    c.update(val)
+                }
+            }
+            Message::ToggleMaximize(maximize) => {
+                self.goal = if maximize {
+                    Goal::MaximizeOutput
+                } else {
+                    Goal::MinimizeCost
+                };
+            }
+            Message::ToggleBuy(item, toggle) => {
+                if toggle {
+                    self.buy_prices.entry(item).or_default();
+                } else {
+                    self.buy_prices.remove(&item);
+                    self.buy_dimension_prices.remove(&item);
+                }
+            }
+            Message::EditBuyPrice(item, val) => {
+                if let Some(c) = self.buy_prices.get_mut(&item) {
+                    c.update(val)
+                }
+            }
+            Message::EditBuyDimensionPrice(item, id, val) => self
+                .buy_dimension_prices
+                .entry(item)
+                .or_default()
+                .entry(id)
+                .or_insert_with(|| ZERO_COST.clone())
+                .update(val),
+            Message::AddRule => self.rules.push((String::new(), "".parse())),
+            Message::EditRule(index, rule) => {
+                let parsed = rule.parse();
+                if let Some(r) = self.rules.get_mut(index) {
+                    *r = (rule, parsed);
+                }
+            }
+            Message::DeleteRule(index) => {
+                if index < self.rules.len() {
+                    let _ = self.rules.remove(index);
+                }
+            }
+            Message::ToggleMaxRecipes(toggle) => {
+                self.max_recipes = toggle.then(|| self.recipes.len().max(1));
+            }
+            Message::EditMaxRecipes(max) => {
+                // A plan with no recipe meets no target.
+                if max > 0 {
+                    self.max_recipes = Some(max)
+                }
+            }
+            Message::ToggleRecipePenalty(toggle) => {
+                self.recipe_penalty = toggle.then(parsed_input::Content::default);
+            }
+            Message::EditRecipePenalty(val) => {
+                if let Some(c) = self.recipe_penalty.as_mut() {
+                    c.update(val)
+                }
+            }
+            Message::ToggleFuel(item, toggle) => {
+                if toggle {
+                    self.fuels.entry(item).or_default();
+                } else {
+                    self.fuels.remove(&item);
+                }
+            }
+            Message::EditFuelSmelts(item, val) => {
+                if let Some(c) = self.fuels.get_mut(&item) {
+                    c.update(val)
+                }
+            }
+            Message::EditNewTag(tag) => {
+                self.new_tag = tag;
+                return Task::none();
+            }
+            Message::AddTag => {
+                let tag = std::mem::take(&mut self.new_tag);
+                if tag.get_name().trim().is_empty() || self.tags.contains_key(&tag) {
+                    return Task::none();
+                }
+                // A tag is a known item, even if no recipe uses it.
+                self.add_known_item(&tag);
+                self.tags.insert(tag, Vec::new());
+            }
+            Message::EditTag(tag, edit) => {
+                let Some(members) = self.tags.get_mut(&tag) else {
+                    return Task::none();
+                };
+                match edit {
+                    TagEdit::Draft(member) => {
+                        self.tag_drafts.insert(tag, member);
+                        return Task::none();
+                    }
+                    TagEdit::AddMember => {
+                        let Some(member) = self.tag_drafts.remove(&tag) else {
+                            return Task::none();
+                        };
+                        if member.get_name().trim().is_empty()
+                            || member == tag
+                            || members.contains(&member)
+                        {
+                            return Task::none();
                         }
-                        recipe::EditableContent::Built(recipe) => {
-                            for (item, qty, product) in
-                                recipe
-                                    .get_ingredients()
-                                    .iter()
-                                    .map(|(item, qty)| (item, *qty as f64, false))
-                                    .chain(recipe.get_products().iter().map(|(item, qty, prob)| {
-                                        (item, (*qty as f64) * prob, true)
-                                    }))
-                            {
-                                match item_expressions.get_mut(item) {
-                                    Some((prod_expr, uses_expr)) => {
-                                        if product {
-                                            prod_expr.add_mul(qty, variables[index])
-                                        } else {
-                                            uses_expr.add_mul(qty, variables[index])
-                                        }
-                                    }
-                                    None => {
-                                        return Task::done(Message::ComputeError(format!(
-                                            "Internal error: no expression found for item {} during expression update with recipe",
-                                            item.get_name()
-                                        )));
-                                    }
+                        members.push(member.clone());
+                        self.add_known_item(&member);
+                    }
+                    TagEdit::RemoveMember(index) => {
+                        if index >= members.len() {
+                            return Task::none();
+                        }
+                        let member = members.remove(index);
+                        self.remove_known_item(&member);
+                    }
+                    TagEdit::Delete => {
+                        let members = self.tags.remove(&tag).unwrap_or_default();
+                        self.tag_drafts.remove(&tag);
+                        for member in members.iter() {
+                            self.remove_known_item(member);
+                        }
+                        self.remove_known_item(&tag);
+                    }
+                }
+            }
+            Message::AddRatioGroup => self.ratio_groups.push(RatioGroup {
+                name: String::new(),
+                amount: parsed_input::Content::default(),
+                members: Vec::new(),
+                priority: 0,
+            }),
+            Message::EditRatioGroup(index, edit) => {
+                if let RatioGroupEdit::Delete = edit {
+                    if index < self.ratio_groups.len() {
+                        self.ratio_groups.remove(index);
+                    }
+                } else if let Some(group) = self.ratio_groups.get_mut(index) {
+                    match edit {
+                        RatioGroupEdit::Name(name) => group.name = name,
+                        RatioGroupEdit::Amount(val) => group.amount.update(val),
+                        RatioGroupEdit::Member(index, member) => {
+                            if index < group.members.len() {
+                                if member.get_name().is_empty() {
+                                    group.members.remove(index);
+                                } else {
+                                    group.members[index].0 = member;
                                 }
+                            } else if !member.get_name().is_empty() {
+                                group
+                                    .members
+                                    .push((member, parsed_input::Content::default()));
                             }
                         }
+                        RatioGroupEdit::Ratio(index, val) => {
+                            if let Some((_, ratio)) = group.members.get_mut(index) {
+                                ratio.update(val)
+                            }
+                        }
+                        RatioGroupEdit::Priority(priority) => group.priority = priority,
+                        RatioGroupEdit::Delete => (),
                     }
                 }
-
-                // Go through the item list and build the constraints / targets / costs
-                let mut total_cost = Expression::default();
-                let mut constraints = Vec::new();
-                for (item, (_, target, raw)) in self.known_items.iter() {
-                    let Some((prod_expr, uses_expr)) = item_expressions.get(&item) else {
-                        return Task::done(Message::ComputeError(format!(
-                            "Internal error: no expression found for item {} during constraint build",
-                            item.get_name()
-                        )));
-                    };
-
-                    let expression = prod_expr.clone() - uses_expr.clone();
-
-                    if let Some(target) = target.as_deref() {
-                        constraints.push((expression >> **target).set_name(item.get_name().clone()))
-                    } else {
-                        match raw {
-                            Some(cost) => total_cost.add_mul(-(***cost), expression),
-                            None => constraints
-                                .push((expression >> 0).set_name(item.get_name().clone())),
-                        }
+            }
+            Message::AddModifier => self.modifiers.push(Modifier {
+                name: String::new(),
+                label: String::new(),
+                enabled: true,
+                products: parsed_input::Content::default(),
+                ingredients: parsed_input::Content::default(),
+            }),
+            Message::EditModifier(index, edit) => {
+                if let ModifierEdit::Delete = edit {
+                    if index < self.modifiers.len() {
+                        self.modifiers.remove(index);
+                    }
+                } else if let Some(modifier) = self.modifiers.get_mut(index) {
+                    match edit {
+                        ModifierEdit::Toggle(enabled) => modifier.enabled = enabled,
+                        ModifierEdit::Name(name) => modifier.name = name,
+                        ModifierEdit::Label(label) => modifier.label = label,
+                        ModifierEdit::Products(val) => modifier.products.update(val),
+                        ModifierEdit::Ingredients(val) => modifier.ingredients.update(val),
+                        ModifierEdit::Delete => (),
                     }
                 }
-
-                // Solve
-                match problem
-                    .minimise(total_cost)
-                    .using(solvers::clarabel::clarabel)
-                    .with_all(constraints)
-                    .solve()
-                {
-                    Ok(solution) => {
-                        self.recipe_uses = Some(
-                            variables
-                                .into_iter()
-                                .map(|var| solution.value(var))
-                                .collect(),
-                        );
-
-                        let item_stats = self.item_stats.insert(HashMap::new());
-
-                        for (item, (prod_expr, uses_expr)) in item_expressions {
-                            let prod = solution.eval(prod_expr);
-                            let uses = solution.eval(uses_expr);
-                            item_stats.insert(item, (prod, uses));
+            }
+            Message::EditCostWeight(val) => self.cost_weight.update(val),
+            Message::AddDimension => {
+                self.dimensions.push(CostDimension {
+                    id: self.next_dimension_id,
+                    name: String::new(),
+                    weight: parsed_input::Content::default(),
+                });
+                self.next_dimension_id += 1;
+            }
+            Message::EditDimension(index, edit) => match edit {
+                DimensionEdit::Name(name) => {
+                    if let Some(d) = self.dimensions.get_mut(index) {
+                        d.name = name;
+                    }
+                }
+                DimensionEdit::Weight(val) => {
+                    if let Some(d) = self.dimensions.get_mut(index) {
+                        d.weight.update(val)
+                    }
+                }
+                DimensionEdit::Delete => {
+                    if index < self.dimensions.len() {
+                        let id = self.dimensions.remove(index).id;
+                        for costs in self.raw_dimension_costs.values_mut() {
+                            costs.remove(&id);
                         }
-
-                        match solution.status() {
-                            good_lp::SolutionStatus::Optimal => (),
-                            status => {
-                                return Task::done(Message::ComputeError(format!(
-                                    "Solution is not optimal. {status:?}"
-                                )));
+                        for prices in self.buy_dimension_prices.values_mut() {
+                            prices.remove(&id);
+                        }
+                        for recipe in self.recipes.iter_mut() {
+                            recipe.perform(recipe::EditableAction::RemoveDimensionCost(id));
+                        }
+                        for axis in [&mut self.pareto_axes.0, &mut self.pareto_axes.1] {
+                            if *axis == Some(id) {
+                                *axis = None;
                             }
                         }
                     }
+                }
+            },
+            Message::EditRawDimensionCost(item, id, val) => self
+                .raw_dimension_costs
+                .entry(item)
+                .or_default()
+                .entry(id)
+                .or_insert_with(|| ZERO_COST.clone())
+                .update(val),
+            Message::Compute => {
+                let model = match self.model() {
+                    Ok(model) => model,
+                    Err(msg) => return Task::done(Message::ComputeError(msg)),
+                };
+                let weights = self.weights();
+                let generation = self.generation;
+                return self.on_worker(model, move |model| {
+                    Message::Solved(generation, model.solve(&weights))
+                });
+            }
+            Message::Solved(generation, result) => {
+                if let Some(task) = self.finish_worker(generation) {
+                    return task;
+                }
+                match result {
+                    Ok(plan) => {
+                        self.load_plan(plan);
+                        if let Some((_, loaded)) = self.pareto.as_mut() {
+                            *loaded = None;
+                        }
+                    }
                     Err(err) => {
-                        return Task::done(Message::ComputeError(format!(
-                            "Could not solve: {err}"
-                        )));
+                        if let SolveError::NotOptimal(plan, _) = &err {
+                            self.load_plan((**plan).clone());
+                        }
+                        return Task::done(Message::ComputeError(err.to_string()));
                     }
                 }
                 self.unsaved_changes = true;
                 return Task::none();
             }
+            Message::CancelCompute => {
+                // The worker is left to stop on its own, and its result is ignored.
+                if let Some(worker) = self.solving.take() {
+                    worker.cancel.store(true, Ordering::Relaxed);
+                    self.generation += 1;
+                }
+                return Task::none();
+            }
+            Message::ToggleLive(live) => {
+                self.live = live;
+                self.live_deadline = None;
+                self.unsaved_changes = true;
+                return Task::done(Message::AutoCompute(self.generation));
+            }
+            Message::LiveTick => {
+                if self
+                    .live_deadline
+                    .is_some_and(|deadline| deadline <= Instant::now())
+                {
+                    self.live_deadline = None;
+                    return Task::done(Message::AutoCompute(self.generation));
+                }
+                return Task::none();
+            }
+            Message::AutoCompute(generation) => {
+                // Only the last edit of a series computes, once everything is valid.
+                if self.live
+                    && generation == self.generation
+                    && self.solving.is_none()
+                    && self.error.is_none()
+                    && self.checks().all()
+                {
+                    return Task::done(Message::Compute);
+                }
+                return Task::none();
+            }
+            Message::SetParetoAxis(y, dimension) => {
+                if y {
+                    self.pareto_axes.1 = dimension;
+                } else {
+                    self.pareto_axes.0 = dimension;
+                }
+                self.pareto = None;
+                self.unsaved_changes = true;
+                return Task::none();
+            }
+            Message::ToggleParetoEpsilon(epsilon) => {
+                self.pareto_method = if epsilon {
+                    ParetoMethod::Epsilon
+                } else {
+                    ParetoMethod::WeightSweep
+                };
+                self.pareto = None;
+                self.unsaved_changes = true;
+                return Task::none();
+            }
+            Message::EditParetoSteps(steps) => {
+                self.pareto_steps = steps.max(1);
+                self.pareto = None;
+                self.unsaved_changes = true;
+                return Task::none();
+            }
+            Message::ComputePareto => {
+                let model = match self.model() {
+                    Ok(model) => model,
+                    Err(msg) => return Task::done(Message::ComputeError(msg)),
+                };
+                let (x, y) = self.pareto_axes;
+                let (method, steps) = (self.pareto_method, self.pareto_steps);
+                let generation = self.generation;
+                return self.on_worker(model, move |model| {
+                    Message::ParetoComputed(generation, model.pareto(x, y, method, steps))
+                });
+            }
+            Message::ParetoComputed(generation, result) => {
+                if let Some(task) = self.finish_worker(generation) {
+                    return task;
+                }
+                match result {
+                    Ok(plans) => self.pareto = Some((plans, None)),
+                    Err(err) => return Task::done(Message::ComputeError(err.to_string())),
+                }
+                return Task::none();
+            }
+            Message::WhyNot(index) => {
+                let model = match self.model() {
+                    Ok(model) => model,
+                    Err(msg) => {
+                        self.why_not = Some((index, Err(msg)));
+                        return Task::none();
+                    }
+                };
+                let weights = self.weights();
+                let generation = self.generation;
+                return self.on_worker(model, move |model| {
+                    let why_not = model
+                        .why_not(&weights, index)
+                        .map_err(|err| err.to_string());
+                    Message::WhyNotComputed(generation, index, why_not)
+                });
+            }
+            Message::WhyNotComputed(generation, index, why_not) => {
+                if let Some(task) = self.finish_worker(generation) {
+                    return task;
+                }
+                self.why_not = Some((index, why_not));
+                return Task::none();
+            }
+            Message::CloseWhyNot => {
+                self.why_not = None;
+                return Task::none();
+            }
+            Message::ComputePriceList => {
+                let model = match self.model() {
+                    Ok(model) => model,
+                    Err(msg) => return Task::done(Message::ComputeError(msg)),
+                };
+                let weights = self.weights();
+                let generation = self.generation;
+                return self.on_worker(model, move |model| {
+                    let price_list = model
+                        .unit_costs(&weights)
+                        .into_iter()
+                        .map(|(item, costs)| (item, costs.map_err(|err| err.to_string())))
+                        .collect();
+                    Message::PriceListComputed(generation, price_list)
+                });
+            }
+            Message::PriceListComputed(generation, price_list) => {
+                if let Some(task) = self.finish_worker(generation) {
+                    return task;
+                }
+                self.price_list = Some(price_list);
+                return Task::none();
+            }
+            Message::ExportPriceList => {
+                let Some(price_list) = self.price_list.as_ref() else {
+                    return Task::none();
+                };
+                let dimensions: Vec<Option<usize>> = Some(None)
+                    .into_iter()
+                    .chain(self.dimensions.iter().map(|d| Some(d.id)))
+                    .collect();
+                let weights = self.weights();
+
+                let mut csv = String::from("Item");
+                for dimension in dimensions.iter() {
+                    csv += &format!(",{}", csv_field(&self.dimension_name(*dimension)));
+                }
+                csv += ",Weighted cost\n";
+                for (item, costs) in price_list.iter() {
+                    csv += &csv_field(item.get_name());
+                    match costs {
+                        Ok(costs) => {
+                            for dimension in dimensions.iter() {
+                                csv += &format!(",{}", costs.get(*dimension));
+                            }
+                            csv += &format!(",{}", costs.weighted(&weights));
+                        }
+                        // The error goes in the first cost column, and the others are left empty.
+                        Err(err) => {
+                            csv += &format!(",{}{}", csv_field(err), ",".repeat(dimensions.len()))
+                        }
+                    }
+                    csv += "\n";
+                }
+
+                let path = FileDialog::new()
+                    .set_directory(
+                        self.path
+                            .as_ref()
+                            .and_then(|path| path.parent())
+                            .map(Path::to_path_buf)
+                            .unwrap_or_else(|| current_dir().unwrap_or_default()),
+                    )
+                    .set_title("Export price list")
+                    .add_filter("CSV", &["csv"])
+                    .set_file_name("price list.csv")
+                    .save_file();
+                if let Some(path) = path
+                    && let Err(err) = std::fs::write(path, csv)
+                {
+                    self.save_error = Some(SaveError::FileError(err));
+                }
+                return Task::none();
+            }
+            Message::ExportModel => {
+                let model = match self.model() {
+                    Ok(model) => model,
+                    Err(msg) => return Task::done(Message::ComputeError(msg)),
+                };
+
+                let path = FileDialog::new()
+                    .set_directory(
+                        self.path
+                            .as_ref()
+                            .and_then(|path| path.parent())
+                            .map(Path::to_path_buf)
+                            .unwrap_or_else(|| current_dir().unwrap_or_default()),
+                    )
+                    .set_title("Export model")
+                    .add_filter("CPLEX LP", &[ModelFormat::Lp.extension()])
+                    .add_filter("MPS", &[ModelFormat::Mps.extension()])
+                    .set_file_name("model.lp")
+                    .save_file();
+                let Some(path) = path else {
+                    return Task::none();
+                };
+                let format = match path.extension().and_then(|ext| ext.to_str()) {
+                    Some(ext) if ext.eq_ignore_ascii_case(ModelFormat::Mps.extension()) => {
+                        ModelFormat::Mps
+                    }
+                    _ => ModelFormat::Lp,
+                };
+                match model.export(&self.weights(), format) {
+                    Ok(program) => {
+                        if let Err(err) = std::fs::write(path, program) {
+                            self.save_error = Some(SaveError::FileError(err));
+                        }
+                    }
+                    Err(err) => self.error = Some(err.to_string()),
+                }
+                return Task::none();
+            }
+            Message::ToggleShare(item, toggle) => {
+                if toggle {
+                    self.expanded_shares.insert(item);
+                } else {
+                    self.expanded_shares.remove(&item);
+                }
+                return Task::none();
+            }
+            Message::LoadParetoPoint(index) => {
+                let plan = self.pareto.as_mut().and_then(|(plans, loaded)| {
+                    let plan = plans.get(index)?.clone();
+                    *loaded = Some(index);
+                    Some(plan)
+                });
+                if let Some(plan) = plan {
+                    self.load_plan(plan);
+                    self.unsaved_changes = true;
+                }
+                return Task::none();
+            }
+            Message::WorkerStopped(generation) => {
+                if let Some(task) = self.finish_worker(generation) {
+                    return task;
+                }
+                return Task::done(Message::ComputeError(
+                    "The solver stopped unexpectedly.".to_string(),
+                ));
+            }
             Message::ComputeError(msg) => {
                 self.error = Some(msg);
                 return Task::none();
@@ -380,7 +1106,15 @@ impl App {
             }
             Message::Open(path_buf) => {
                 match Self::from_file(path_buf) {
-                    Ok(app) => *self = app,
+                    Ok(app) => {
+                        // Results of a solve of the previous project are outdated.
+                        if let Some(worker) = self.solving.as_ref() {
+                            worker.cancel.store(true, Ordering::Relaxed);
+                        }
+                        let generation = self.generation + 1;
+                        *self = app;
+                        self.generation = generation;
+                    }
                     Err(err) => self.import_error = Some(err),
                 }
                 return Task::none();
@@ -407,11 +1141,365 @@ impl App {
         }
         self.unsaved_changes = true;
         self.error = None;
+        // The computation running is outdated. It stays until its thread finishes, so only one runs at a time.
+        if let Some(worker) = self.solving.as_ref() {
+            worker.cancel.store(true, Ordering::Relaxed);
+        }
+        self.generation += 1;
+        self.pareto = None;
+        self.price_list = None;
+        self.why_not = None;
+        if self.live && self.checks().all() {
+            // The results stay until the new ones replace them.
+            self.live_deadline = Some(Instant::now() + LIVE_DELAY);
+            return Task::none();
+        }
+        self.live_deadline = None;
         self.recipe_uses = None;
         self.item_stats = None;
+        self.bought = None;
+        self.burnt = None;
+        self.tag_uses = None;
+        self.shares = None;
+        self.pruned = None;
         Task::none()
     }
 
+    /// Runs blocking work on the model, like the solver, on its own thread. The message it returns comes back once it
+    /// is done, and has to go through [`finish_worker`](Self::finish_worker).
+    ///
+    /// Cancelling stops the model before its next optimization, but an optimization that already started runs to its
+    /// end. A computation that was only cancelled because of an edit keeps the worker busy until then; one that the user
+    /// cancelled is detached at once.
+    fn on_worker(
+        &mut self,
+        mut model: Model,
+        work: impl FnOnce(&Model) -> Message + Send + 'static,
+    ) -> Task<Message> {
+        let cancel = Arc::new(AtomicBool::new(false));
+        model.cancel = cancel.clone();
+        let (sender, receiver) = oneshot::channel();
+        thread::spawn(move || {
+            let _ = sender.send(work(&model));
+        });
+        let generation = self.generation;
+        self.solving = Some(Worker { generation, cancel });
+        self.error = None;
+        Task::perform(receiver, move |message| {
+            message.unwrap_or(Message::WorkerStopped(generation))
+        })
+    }
+
+    /// Marks the computation of this generation of the model as finished. Returns what to do instead of using its
+    /// result, if it is not wanted anymore: it was cancelled or detached, or the model changed since.
+    fn finish_worker(&mut self, generation: u64) -> Option<Task<Message>> {
+        let Some(worker) = self
+            .solving
+            .take_if(|worker| worker.generation == generation)
+        else {
+            // Detached by a cancel or by opening another project, so nothing waits for it.
+            return Some(Task::none());
+        };
+        let cancelled = worker.cancel.load(Ordering::Relaxed);
+        if generation != self.generation {
+            // The edits made meanwhile could not compute in live mode while it was running.
+            Some(Task::done(Message::AutoCompute(self.generation)))
+        } else if cancelled {
+            Some(Task::none())
+        } else {
+            None
+        }
+    }
+
+    /// Builds the model of the optimizer from the recipes and items.
+    fn model(&self) -> Result<Model, String> {
+        let mut recipes = Vec::new();
+        for (index, recipe) in self.recipes.iter().enumerate() {
+            match recipe {
+                recipe::EditableContent::Builder(_) => {
+                    return Err("One of the recipies is not build.".to_string());
+                }
+                recipe::EditableContent::Built(recipe) => {
+                    // Multipliers of the enabled modifiers that apply to this recipe.
+                    let (products_mult, ingredients_mult) = self
+                        .modifiers
+                        .iter()
+                        .filter(|modifier| modifier.applies_to(recipe))
+                        .fold((1., 1.), |(products, ingredients), modifier| {
+                            (
+                                products * **modifier.products,
+                                ingredients * **modifier.ingredients,
+                            )
+                        });
+                    let (min_uses, max_uses) = match self.uses_locks.get(index) {
+                        Some(Some(lock)) if lock.range => (**lock.min, Some(**lock.max)),
+                        Some(Some(lock)) => (**lock.min, Some(**lock.min)),
+                        _ => (0., None),
+                    };
+                    recipes.push(ModelRecipe {
+                        recipe: recipe.clone(),
+                        products_mult,
+                        ingredients_mult,
+                        min_uses,
+                        max_uses,
+                    });
+                }
+            }
+        }
+
+        let items = self
+            .known_items
+            .iter()
+            .map(|(item, (_, target, raw))| {
+                let role = match (target, raw) {
+                    (Some(target), _) => ItemRole::Target {
+                        amount: ***target,
+                        priority: self
+                            .target_priorities
+                            .get(item)
+                            .copied()
+                            .unwrap_or_default(),
+                    },
+                    (None, Some(cost)) => ItemRole::Raw {
+                        costs: Costs {
+                            main: ***cost,
+                            dimensions: self
+                                .raw_dimension_costs
+                                .get(item)
+                                .map(|costs| costs.iter().map(|(id, c)| (*id, ***c)).collect())
+                                .unwrap_or_default(),
+                        },
+                        available: self.raw_limits.get(item).map(|limit| ***limit),
+                    },
+                    (None, None) => ItemRole::Intermediate,
+                };
+                (item.clone(), role)
+            })
+            .collect();
+
+        let mut ratio_groups = Vec::new();
+        for group in self.ratio_groups.iter() {
+            if let Some((item, _)) = group
+                .members
+                .iter()
+                .find(|(item, _)| !self.known_items.contains_key(item))
+            {
+                return Err(format!(
+                    "{} of ratio group {} is in no recipe.",
+                    item.get_name(),
+                    group.name
+                ));
+            }
+            ratio_groups.push(optimizer::RatioGroup {
+                members: group
+                    .members
+                    .iter()
+                    .map(|(item, ratio)| (item.clone(), ***ratio))
+                    .collect(),
+                amount: **group.amount,
+                priority: group.priority,
+            });
+        }
+
+        let rules = self
+            .rules
+            .iter()
+            .map(|(text, rule)| {
+                rule.clone()
+                    .map_err(|err| format!("In constraint {text}: {err}"))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Model {
+            recipes,
+            items,
+            fuels: self
+                .fuels
+                .iter()
+                .map(|(item, smelts)| (item.clone(), ***smelts))
+                .collect(),
+            max_production: self
+                .max_production
+                .iter()
+                .map(|(item, max)| (item.clone(), ***max))
+                .collect(),
+            max_surplus: self
+                .max_surplus
+                .iter()
+                .map(|(item, max)| (item.clone(), ***max))
+                .collect(),
+            tags: self.tags.clone(),
+            ratio_groups,
+            prices: self
+                .buy_prices
+                .iter()
+                .map(|(item, price)| {
+                    let costs = Costs {
+                        main: ***price,
+                        dimensions: self
+                            .buy_dimension_prices
+                            .get(item)
+                            .map(|prices| prices.iter().map(|(id, c)| (*id, ***c)).collect())
+                            .unwrap_or_default(),
+                    };
+                    (item.clone(), costs)
+                })
+                .collect(),
+            dimensions: self.dimensions.iter().map(|d| d.id).collect(),
+            goal: self.goal,
+            max_recipes: self.max_recipes,
+            recipe_penalty: self.recipe_penalty.as_ref().map(|penalty| ***penalty),
+            rules,
+            cancel: Default::default(),
+        })
+    }
+
+    /// Weight of each cost dimension in the objective.
+    fn weights(&self) -> Costs {
+        Costs {
+            main: **self.cost_weight,
+            dimensions: self.dimensions.iter().map(|d| (d.id, **d.weight)).collect(),
+        }
+    }
+
+    /// Checks that every part of the project is ready to be computed.
+    fn checks(&self) -> Checks {
+        let targets = self
+            .known_items
+            .values()
+            .filter_map(|(_, target, _)| target.as_ref());
+        let targets_ok = (targets.clone().count() > 0 || !self.ratio_groups.is_empty())
+            && targets.clone().all(|c| c.is_valid())
+            && self.ratio_groups.iter().all(|group| {
+                group.amount.is_valid()
+                    && group.members.iter().all(|(_, ratio)| ratio.is_valid())
+                    && group.members.iter().any(|(_, ratio)| ***ratio > 0.)
+            });
+        let supplies_ok = self.fuels.values().all(|c| c.is_valid())
+            && self.buy_prices.values().all(|c| c.is_valid())
+            && self.max_production.values().all(|c| c.is_valid())
+            && self.max_surplus.values().all(|c| c.is_valid());
+        let dimensions_ok = self.cost_weight.is_valid()
+            && self.dimensions.iter().all(|d| d.weight.is_valid())
+            && self
+                .raw_dimension_costs
+                .values()
+                .chain(self.buy_dimension_prices.values())
+                .flat_map(|costs| costs.values())
+                .all(|c| c.is_valid());
+        let modifiers_ok = self
+            .modifiers
+            .iter()
+            .all(|m| m.products.is_valid() && m.ingredients.is_valid())
+            && self.recipe_penalty.as_ref().is_none_or(|c| c.is_valid());
+        let locks_ok = self.uses_locks.iter().flatten().all(|lock| {
+            lock.min.is_valid()
+                && (!lock.range || (lock.max.is_valid() && **lock.min <= **lock.max))
+        });
+        let rules_ok = self.rules.iter().all(|(_, rule)| rule.is_ok());
+        let raws = self
+            .known_items
+            .values()
+            .filter_map(|(_, _, raw)| raw.as_ref());
+        let raws_ok = raws.clone().count() > 0
+            && raws.clone().all(|c| c.is_valid())
+            && self.raw_limits.values().all(|c| c.is_valid());
+        let recipes_ok = !self.recipes.is_empty()
+            && self.recipes.iter().all(|recipe| match recipe {
+                recipe::EditableContent::Builder(_) => false,
+                recipe::EditableContent::Built(_) => true,
+            });
+        Checks {
+            recipes: recipes_ok,
+            targets: targets_ok,
+            raws: raws_ok,
+            supplies: supplies_ok,
+            modifiers: modifiers_ok,
+            dimensions: dimensions_ok,
+            rules: rules_ok,
+            locks: locks_ok,
+        }
+    }
+
+    /// Name of a cost dimension. `None` is the main one.
+    fn dimension_name(&self, dimension: Option<usize>) -> String {
+        match dimension {
+            None => "Cost".to_string(),
+            Some(id) => self
+                .dimensions
+                .iter()
+                .find(|d| d.id == id)
+                .map(|d| d.name.clone())
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| format!("Dimension {id}")),
+        }
+    }
+
+    /// Shares of the targets in the saved results, if they are complete enough to follow the flows.
+    fn saved_shares(&self) -> Option<BTreeMap<Item, Share>> {
+        let plan = Plan {
+            recipe_uses: self.recipe_uses.clone()?,
+            item_stats: self.item_stats.clone()?,
+            bought: self.bought.clone().unwrap_or_default(),
+            burnt: self.burnt.clone()?,
+            tag_uses: self.tag_uses.clone()?,
+            ..Default::default()
+        };
+        self.model().ok().map(|model| model.shares(&plan))
+    }
+
+    fn load_plan(&mut self, plan: Plan) {
+        self.shares = self.model().ok().map(|model| model.shares(&plan));
+        self.recipe_uses = Some(plan.recipe_uses);
+        self.item_stats = Some(plan.item_stats);
+        self.bought = Some(plan.bought);
+        self.burnt = Some(plan.burnt);
+        self.tag_uses = Some(plan.tag_uses);
+        self.pruned = Some((plan.pruned_recipes, plan.pruned_items));
+    }
+
+    /// Items of the recipe at this index, ingredients then products, each once per time it appears. Empty if the
+    /// recipe is being edited.
+    fn recipe_items(&self, index: usize) -> Vec<Item> {
+        match self.recipes.get(index) {
+            Some(recipe::EditableContent::Built(recipe)) => recipe
+                .get_ingredients()
+                .iter()
+                .map(|(item, _)| item)
+                .chain(recipe.get_products().iter().map(|(item, _, _)| item))
+                .cloned()
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn add_known_item(&mut self, item: &Item) {
+        match self.known_items.get_mut(item) {
+            Some((qty, _, _)) => *qty += 1,
+            None => {
+                self.known_items.insert(item.clone(), (0, None, None));
+            }
+        }
+    }
+
+    fn remove_known_item(&mut self, item: &Item) {
+        if let Some((qty, _, _)) = self.known_items.get_mut(item) {
+            if *qty == 0 {
+                self.known_items.remove(item);
+                self.fuels.remove(item);
+                self.raw_dimension_costs.remove(item);
+                self.raw_limits.remove(item);
+                self.target_priorities.remove(item);
+                self.buy_prices.remove(item);
+                self.buy_dimension_prices.remove(item);
+                self.max_production.remove(item);
+                self.max_surplus.remove(item);
+            } else {
+                *qty -= 1
+            }
+        }
+    }
+
     fn save_popup(&mut self, msg: Message) -> Task<Message> {
         if self.unsaved_changes {
             self.save_popup = Some(msg);
@@ -435,27 +1523,153 @@ impl App {
 
         // Recipes
 
+        // Short description of a recipe, to tell it apart from the others.
+        let recipe_summary = |index: usize| match self.recipes.get(index) {
+            Some(recipe::EditableContent::Built(recipe)) => {
+                let names = |items: Vec<&Item>| {
+                    items
+                        .iter()
+                        .map(|item| item.get_name().as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                format!(
+                    "{} from {}",
+                    names(
+                        recipe
+                            .get_products()
+                            .iter()
+                            .map(|(item, _, _)| item)
+                            .collect()
+                    ),
+                    names(
+                        recipe
+                            .get_ingredients()
+                            .iter()
+                            .map(|(item, _)| item)
+                            .collect()
+                    )
+                )
+            }
+            _ => format!("Recipe {}", index + 1),
+        };
+        let why_not = |index: usize| {
+            let (_, why_not) = self.why_not.as_ref().filter(|(i, _)| *i == index)?;
+            let mut col = column![title_text(TitleLevel::SubSectionTitle, "Why not?")];
+            match why_not {
+                Err(err) => col = col.push(text(err.clone())),
+                Ok(why_not) if why_not.pruned => {
+                    col = col.push(text("It makes nothing the targets need."))
+                }
+                Ok(why_not) => {
+                    col = col.push(text(if why_not.uses > 1e-6 {
+                        format!(
+                            "It is used {} times when the recipes used are not counted.",
+                            DisplayFloat::new(why_not.uses)
+                        )
+                    } else if why_not.reduced_cost > 1e-6 {
+                        format!(
+                            "One craft would need to be {} cheaper to enter the plan.",
+                            DisplayFloat::new(why_not.reduced_cost)
+                        )
+                    } else {
+                        "It would not make the plan more expensive: another plan of the same cost \
+                         uses it."
+                            .to_string()
+                    }));
+                    col = col.push(text!(
+                        "One craft costs {} and makes products worth {}.",
+                        DisplayFloat::new(why_not.craft_cost),
+                        DisplayFloat::new(why_not.products_value)
+                    ));
+                    if why_not.constraints_cost.abs() > 1e-6 {
+                        col = col.push(text!(
+                            "The rules and limits it is in add {} per craft.",
+                            DisplayFloat::new(why_not.constraints_cost)
+                        ));
+                    }
+                    col = col.push(text("Cost of one more, in the plan:"));
+                    for (item, cost) in why_not.item_costs.iter() {
+                        col = col.push(text!(
+                            "    {}: {}",
+                            item.get_name(),
+                            DisplayFloat::new(*cost)
+                        ));
+                    }
+                    if let Some(recipe::EditableContent::Built(recipe)) = self.recipes.get(index)
+                        && recipe.get_fuel().is_some()
+                    {
+                        col = col.push(text!(
+                            "    Smelting operation: {}",
+                            DisplayFloat::new(why_not.smelt_cost)
+                        ));
+                    }
+                    if !why_not.routes.is_empty() {
+                        col = col.push(text("Made instead by:"));
+                        for route in why_not.routes.iter() {
+                            col = col.push(text!("    {}", recipe_summary(*route)));
+                        }
+                    }
+                }
+            }
+            Some(
+                col.push(button(text("Close")).on_press(Message::CloseWhyNot))
+                    .spacing(SPACE),
+            )
+        };
+
         let recipes = self.recipes.iter().enumerate().map(|(index, recipe)| {
             contoured(
                 ContextMenu::new(
-                    row![
-                        recipe::EditableWidget::new(recipe, move |a| Message::Action(index, a))
-                            .build_button(Message::Build(index))
-                    ]
-                    .push_maybe(
-                        self.recipe_uses
-                            .as_ref()
-                            .and_then(|vec| vec.get(index))
-                            .map(|nb| {
+                    column![
+                        row![
+                            recipe::EditableWidget::new(recipe, move |a| Message::Action(index, a))
+                                .build_button(Message::Build(index))
+                                .dimensions(
+                                    self.dimensions
+                                        .iter()
+                                        .map(|d| (d.id, d.name.as_str()))
+                                        .collect()
+                                )
+                        ]
+                        .push_maybe({
+                            let nb = self.recipe_uses.as_ref().and_then(|vec| vec.get(index));
+                            let lock = self.uses_locks.get(index).and_then(Option::as_ref);
+                            (nb.is_some() || lock.is_some()).then(|| {
+                                let edit = move |edit| Message::EditUsesLock(index, edit);
                                 column![
                                     title_text(TitleLevel::SubSectionTitle, "Uses"),
                                     widther(),
                                     horizontal_rule(SPACE),
-                                    scale_field("nb", *nb)
                                 ]
+                                .push_maybe(nb.map(|nb| scale_field("nb", *nb)))
+                                .push_maybe(lock.map(|lock| {
+                                    column![
+                                        Checkbox::new("Range", lock.range)
+                                            .on_toggle(move |v| edit(UsesLockEdit::Range(v))),
+                                        ParsedInput::new(
+                                            if lock.range { "Min" } else { "Locked" },
+                                            &lock.min
+                                        )
+                                        .on_input(move |v| edit(UsesLockEdit::Min(v)))
+                                        .style(parsed_input::danger_on_err(text_input::default)),
+                                    ]
+                                    .push_maybe(lock.range.then(|| {
+                                        ParsedInput::new("Max", &lock.max)
+                                            .on_input(move |v| edit(UsesLockEdit::Max(v)))
+                                            .style(parsed_input::danger_on_err(danger_if(
+                                                **lock.max < **lock.min,
+                                            )))
+                                    }))
+                                    .spacing(SPACE)
+                                }))
+                                .spacing(SPACE)
                                 .width(Shrink)
-                            }),
-                    )
+                            })
+                        })
+                        .spacing(SPACE)
+                    ]
+                    .push_maybe(why_not(index))
                     .spacing(SPACE),
                     move || {
                         let mut res = Column::new();
@@ -472,6 +1686,23 @@ impl App {
                                 res.push(button(text("Edit recipe")).on_press(Message::Edit(index)))
                         };
 
+                        let unused = self
+                            .recipe_uses
+                            .as_ref()
+                            .and_then(|uses| uses.get(index))
+                            .is_some_and(|uses| *uses <= 1e-6);
+                        if unused {
+                            res = res.push(button(text("Why not?")).on_press_maybe(
+                                self.solving.is_none().then_some(Message::WhyNot(index)),
+                            ))
+                        }
+
+                        let locked = matches!(self.uses_locks.get(index), Some(Some(_)));
+                        res = res.push(
+                            button(text(if locked { "Unlock uses" } else { "Lock uses" }))
+                                .on_press(Message::ToggleUsesLock(index, !locked)),
+                        );
+
                         res.push(
                             button(text("Delete recipe"))
                                 .on_press(Message::Delete(index))
@@ -503,22 +1734,33 @@ impl App {
         let mut targets = Vec::new();
         let mut raws = Vec::new();
         for (item, (_, target, raw)) in all.iter() {
-            raw.as_ref().map(|cost| raws.push((item, cost)));
-            target.as_ref().map(|t| targets.push((item, t)));
+            if let Some(cost) = raw.as_ref() {
+                raws.push((item, cost))
+            }
+            if let Some(t) = target.as_ref() {
+                targets.push((item, t))
+            }
         }
 
-        let all_targets_ok = targets.len() > 0 && targets.iter().all(|(_, c)| (*c).is_valid());
-        let all_raws_ok = raws.len() > 0 && raws.iter().all(|(_, c)| (*c).is_valid());
+        let checks = self.checks();
+        let can_compute = self.error.is_none() && checks.all();
 
         // Item details
 
         let details = Scrollable::new({
+            let maximize = self.goal == Goal::MaximizeOutput;
             let mut targets_rows = vec![
                 {
                     let mut row = element_vec![
                         title_text(TitleLevel::SubSectionTitle, "Targets"),
-                        text("Target amount")
+                        text(match self.goal {
+                            Goal::MinimizeCost => "Target amount",
+                            Goal::MaximizeOutput => "Weight",
+                        })
                     ];
+                    if maximize {
+                        row.push("Priority")
+                    }
                     if self.item_stats.is_some() {
                         row.push("Net production")
                     }
@@ -526,6 +1768,9 @@ impl App {
                 },
                 {
                     let mut row = element_vec![Space::new(Shrink, Shrink), widther(),];
+                    if maximize {
+                        row.push(widther())
+                    }
                     if self.item_stats.is_some() {
                         row.push(widther())
                     }
@@ -533,24 +1778,316 @@ impl App {
                 },
                 {
                     let mut row = element_vec![horizontal_rule(SPACE), horizontal_rule(SPACE)];
+                    if maximize {
+                        row.push(horizontal_rule(SPACE))
+                    }
                     if self.item_stats.is_some() {
                         row.push(horizontal_rule(SPACE))
                     }
                     row
                 },
             ];
-            targets_rows.extend(targets.into_iter().map(|(item, amount)| {
+            // Raw materials, purchases and costs that go into an item, indented by `indent`.
+            let share_rows = |item: &Item, indent: u16| {
+                let Some(share) = self.shares.as_ref().and_then(|shares| shares.get(item)) else {
+                    return Vec::new();
+                };
+                let mut lines: Vec<(Element<'_, Message>, &str, f64)> = Vec::new();
+                lines.extend(
+                    share
+                        .raws
+                        .iter()
+                        .map(|(raw, amount)| (raw.displayer(), "Raw material", *amount)),
+                );
+                lines.extend(share.bought.iter().map(|(bought, amount)| {
+                    (
+                        row![bought.displayer(), text("bought")]
+                            .spacing(SPACE)
+                            .into(),
+                        "Bought",
+                        *amount,
+                    )
+                }));
+                for dimension in Some(None)
+                    .into_iter()
+                    .chain(self.dimensions.iter().map(|d| Some(d.id)))
+                {
+                    lines.push((
+                        text(self.dimension_name(dimension)).into(),
+                        "Cost",
+                        share.costs.get(dimension),
+                    ));
+                }
+                if !self.dimensions.is_empty() {
+                    lines.push((
+                        text("Weighted cost").into(),
+                        "Weighted cost",
+                        share.costs.weighted(&self.weights()),
+                    ));
+                }
+                let mut rows: Vec<_> = lines
+                    .into_iter()
+                    .map(|(name, placeholder, value)| {
+                        let mut row = element_vec![
+                            row![Space::new(indent, Shrink), name],
+                            Space::new(Shrink, Shrink)
+                        ];
+                        if maximize {
+                            row.push(Space::new(Shrink, Shrink))
+                        }
+                        row.push(scale_field(placeholder, value));
+                        row
+                    })
+                    .collect();
+                if share.incomplete {
+                    let mut row = element_vec![
+                        row![
+                            Space::new(indent, Shrink),
+                            text("A loop of recipes was cut short, so this share is incomplete.")
+                                .style(text::danger)
+                        ],
+                        Space::new(Shrink, Shrink)
+                    ];
+                    if maximize {
+                        row.push(Space::new(Shrink, Shrink))
+                    }
+                    row.push(Space::new(Shrink, Shrink));
+                    rows.push(row);
+                }
+                rows
+            };
+            // Weighted output of each priority level.
+            let mut level_outputs: BTreeMap<u32, f64> = BTreeMap::new();
+            for (item, amount) in targets.into_iter() {
+                let mut row = element_vec![
+                    item.displayer(),
+                    ParsedInput::new(
+                        match self.goal {
+                            Goal::MinimizeCost => "Amount per batch",
+                            Goal::MaximizeOutput => "Weight",
+                        },
+                        amount
+                    )
+                    .on_input(|v| Message::EditTargetAmount((**item).clone(), v))
+                    .style(parsed_input::danger_on_err(text_input::default))
+                ];
+                let priority = self
+                    .target_priorities
+                    .get(*item)
+                    .copied()
+                    .unwrap_or_default();
+                if maximize {
+                    row.push(
+                        TypedInput::new("Priority", &priority)
+                            .on_input(|v| Message::EditTargetPriority((**item).clone(), v)),
+                    )
+                }
+                if let Some((prod, uses)) = self.item_stats.as_ref().and_then(|tbl| tbl.get(item)) {
+                    *level_outputs.entry(priority).or_default() += ***amount * (prod - uses);
+                    row.push(scale_field("Net production", prod - uses))
+                }
+                let share = self.shares.as_ref().and_then(|shares| shares.get(*item));
+                let expanded = self.expanded_shares.contains(*item);
+                if share.is_some() {
+                    row.push(
+                        button(text(if expanded { "Hide share" } else { "Share" }))
+                            .on_press(Message::ToggleShare((**item).clone(), !expanded)),
+                    )
+                }
+                targets_rows.push(row);
+
+                if expanded {
+                    targets_rows.extend(share_rows(item, SPACE));
+                }
+            }
+            for (index, group) in self.ratio_groups.iter().enumerate() {
+                let edit = move |edit| Message::EditRatioGroup(index, edit);
+                // Complete sets produced: the member that runs out first limits them.
+                let sets = self.item_stats.as_ref().map(|tbl| {
+                    group
+                        .members
+                        .iter()
+                        .map(|(item, ratio)| {
+                            tbl.get(item)
+                                .map(|(prod, uses)| prod - uses)
+                                .unwrap_or_default()
+                                / ***ratio
+                        })
+                        .fold(f64::INFINITY, f64::min)
+                });
+                let sets = sets.filter(|sets| sets.is_finite());
+
                 let mut row = element_vec![
-                    item.displayer(),
-                    ParsedInput::new("Amount per batch", amount)
-                        .on_input(|v| Message::EditTargetAmount((**item).clone(), v))
-                        .style(parsed_input::danger_on_err(text_input::default))
+                    text_input("Ratio group", &group.name)
+                        .on_input(move |v| edit(RatioGroupEdit::Name(v))),
+                    ParsedInput::new(
+                        match self.goal {
+                            Goal::MinimizeCost => "Sets",
+                            Goal::MaximizeOutput => "Weight per set",
+                        },
+                        &group.amount
+                    )
+                    .on_input(move |v| edit(RatioGroupEdit::Amount(v)))
+                    .style(parsed_input::danger_on_err(text_input::default))
                 ];
-                if let Some((prod, uses)) = self.item_stats.as_ref().and_then(|tbl| tbl.get(item)) {
-                    row.push(scale_field("Net production", prod - uses))
+                if maximize {
+                    row.push(
+                        TypedInput::new("Priority", &group.priority)
+                            .on_input(move |v| edit(RatioGroupEdit::Priority(v))),
+                    )
                 }
-                row
-            }));
+                match sets {
+                    Some(sets) => {
+                        *level_outputs.entry(group.priority).or_default() += **group.amount * sets;
+                        row.push(scale_field("Complete sets", sets))
+                    }
+                    None if group.members.is_empty() => row.push(text("Add a member")),
+                    None => row.push(Space::new(Shrink, Shrink)),
+                }
+                row.push(
+                    button(text("Delete"))
+                        .on_press(edit(RatioGroupEdit::Delete))
+                        .style(button::danger),
+                );
+                targets_rows.push(row);
+
+                targets_rows.extend(
+                    group
+                        .members
+                        .iter()
+                        .map(|(item, ratio)| (item, Some(ratio)))
+                        .chain([(EMPTY_ITEM.deref(), None)])
+                        .enumerate()
+                        .flat_map(|(member_index, (item, ratio))| {
+                            let mut row = element_vec![row![
+                                Space::new(SPACE, Shrink),
+                                item.builder(
+                                    move |m| edit(RatioGroupEdit::Member(member_index, m)),
+                                    None
+                                )
+                            ]];
+                            if let Some(ratio) = ratio {
+                                row.push(
+                                    ParsedInput::new("Per set", ratio)
+                                        .on_input(move |v| {
+                                            edit(RatioGroupEdit::Ratio(member_index, v))
+                                        })
+                                        .style(parsed_input::danger_on_err(text_input::default)),
+                                );
+                                if maximize {
+                                    row.push(Space::new(Shrink, Shrink))
+                                }
+                                if let Some((prod, uses)) =
+                                    self.item_stats.as_ref().and_then(|tbl| tbl.get(item))
+                                {
+                                    row.push(scale_field("Net production", prod - uses))
+                                }
+                            }
+
+                            // The share of a member that is also a target is shown with the targets.
+                            let target = self
+                                .known_items
+                                .get(item)
+                                .is_some_and(|(_, target, _)| target.is_some());
+                            let share = self
+                                .shares
+                                .as_ref()
+                                .and_then(|shares| shares.get(item))
+                                .filter(|_| ratio.is_some() && !target);
+                            let expanded = self.expanded_shares.contains(item);
+                            if share.is_some() {
+                                row.push(
+                                    button(text(if expanded { "Hide share" } else { "Share" }))
+                                        .on_press(Message::ToggleShare(item.clone(), !expanded)),
+                                )
+                            }
+                            let mut rows = vec![row];
+                            if share.is_some() && expanded {
+                                rows.extend(share_rows(item, 2 * SPACE));
+                            }
+                            rows
+                        }),
+                );
+            }
+            targets_rows.push(element_vec![
+                button(text("Add ratio group")).on_press(Message::AddRatioGroup)
+            ]);
+            if maximize && self.item_stats.is_some() {
+                // Highest priority first, as in the sequence of optimizations.
+                if level_outputs.len() > 1 {
+                    targets_rows.push(element_vec![text(
+                        "Each priority level was maximized in turn, from the highest, \
+                         keeping the output of the levels above."
+                    )]);
+                    targets_rows.extend(level_outputs.iter().rev().map(|(priority, output)| {
+                        element_vec![
+                            text!("Priority {priority} output:"),
+                            Space::new(Shrink, Shrink),
+                            Space::new(Shrink, Shrink),
+                            scale_field("Weighted output", *output)
+                        ]
+                    }));
+                }
+                targets_rows.push(element_vec![
+                    text("Weighted output:"),
+                    Space::new(Shrink, Shrink),
+                    Space::new(Shrink, Shrink),
+                    scale_field("Weighted output", level_outputs.values().sum())
+                ]);
+            }
+            targets_rows.push(element_vec![
+                Checkbox::new(
+                    "Maximize output within the available raw materials",
+                    self.goal == Goal::MaximizeOutput
+                )
+                .on_toggle(Message::ToggleMaximize)
+            ]);
+
+            let mut dimensions_rows = vec![
+                element_vec![
+                    title_text(TitleLevel::SubSectionTitle, "Cost dimensions"),
+                    text("Weight"),
+                    Space::new(Shrink, Shrink)
+                ],
+                element_vec![
+                    Space::new(Shrink, Shrink),
+                    widther(),
+                    Space::new(Shrink, Shrink)
+                ],
+                element_vec![
+                    horizontal_rule(SPACE),
+                    horizontal_rule(SPACE),
+                    horizontal_rule(SPACE)
+                ],
+                element_vec![
+                    text("Cost of one"),
+                    ParsedInput::new("Weight", &self.cost_weight)
+                        .on_input(Message::EditCostWeight)
+                        .style(parsed_input::danger_on_err(text_input::default)),
+                    Space::new(Shrink, Shrink)
+                ],
+            ];
+            dimensions_rows.extend(
+                self.dimensions
+                    .iter()
+                    .enumerate()
+                    .map(|(index, dimension)| {
+                        let edit = move |edit| Message::EditDimension(index, edit);
+                        element_vec![
+                            text_input("Name", &dimension.name)
+                                .on_input(move |v| edit(DimensionEdit::Name(v))),
+                            ParsedInput::new("Weight", &dimension.weight)
+                                .on_input(move |v| edit(DimensionEdit::Weight(v)))
+                                .style(parsed_input::danger_on_err(text_input::default)),
+                            button(text("Delete"))
+                                .on_press(edit(DimensionEdit::Delete))
+                                .style(button::danger)
+                        ]
+                    }),
+            );
+            dimensions_rows.push(element_vec![
+                button(text("Add dimension")).on_press(Message::AddDimension)
+            ]);
 
             let mut raws_rows = vec![
                 {
@@ -558,6 +2095,8 @@ impl App {
                         title_text(TitleLevel::SubSectionTitle, "Raw materials").width(Shrink),
                         text("Cost of one")
                     ];
+                    row.extend(self.dimensions.iter().map(|d| text(&d.name)));
+                    row.push("Available");
                     if self.item_stats.is_some() {
                         row.push("Required");
                         row.push("Cost");
@@ -568,6 +2107,8 @@ impl App {
                 },
                 {
                     let mut row = element_vec![Space::new(Shrink, Shrink), widther(),];
+                    row.extend(self.dimensions.iter().map(|_| widther()));
+                    row.push(widther());
                     if self.item_stats.is_some() {
                         row.extend([widther(), widther(), widther(), widther()])
                     }
@@ -575,6 +2116,8 @@ impl App {
                 },
                 {
                     let mut row = element_vec![horizontal_rule(SPACE), horizontal_rule(SPACE)];
+                    row.extend(self.dimensions.iter().map(|_| horizontal_rule(SPACE)));
+                    row.push(horizontal_rule(SPACE));
                     if self.item_stats.is_some() {
                         row.push(horizontal_rule(SPACE));
                         row.push(horizontal_rule(SPACE));
@@ -584,11 +2127,12 @@ impl App {
                     row
                 },
             ];
-            
+
             let mut total_required = 0.;
             let mut total_cost = 0.;
             let mut total_used = 0.;
             let mut total_produced = 0.;
+            let mut dimension_totals = vec![0.; self.dimensions.len()];
 
             raws_rows.extend(raws.into_iter().map(|(item, cost)| {
                 let mut row = element_vec![
@@ -597,14 +2141,46 @@ impl App {
                         .on_input(|v| Message::EditRawCost((**item).clone(), v))
                         .style(parsed_input::danger_on_err(text_input::default))
                 ];
+                let dimension_cost = |id: usize| {
+                    self.raw_dimension_costs
+                        .get(*item)
+                        .and_then(|costs| costs.get(&id))
+                        .unwrap_or(&ZERO_COST)
+                };
+                row.extend(self.dimensions.iter().map(|d| {
+                    let (id, item) = (d.id, (**item).clone());
+                    ParsedInput::new(&d.name, dimension_cost(id))
+                        .on_input(move |v| Message::EditRawDimensionCost(item.clone(), id, v))
+                        .style(parsed_input::danger_on_err(text_input::default))
+                }));
+                let limit = self.raw_limits.get(*item);
+                row.push(
+                    row![
+                        Checkbox::new("", limit.is_some())
+                            .on_toggle(|v| Message::ToggleRawLimit((**item).clone(), v))
+                    ]
+                    .push_maybe(limit.map(|limit| {
+                        ParsedInput::new("Available", limit)
+                            .on_input(|v| Message::EditRawLimit((**item).clone(), v))
+                            .style(parsed_input::danger_on_err(text_input::default))
+                    }))
+                    .spacing(SPACE)
+                    .align_y(iced::Alignment::Center),
+                );
                 if let Some((prod, uses)) = self.item_stats.as_ref().and_then(|tbl| tbl.get(item)) {
-                    let cost_items = ***cost * (uses - prod);
+                    // Only the amount taken is paid for, so a surplus costs nothing.
+                    let taken = (uses - prod).max(0.);
+                    let cost_items = ***cost * taken;
+
+                    for (total, d) in dimension_totals.iter_mut().zip(self.dimensions.iter()) {
+                        *total += ***dimension_cost(d.id) * taken;
+                    }
 
                     total_required += uses - prod;
                     total_cost += cost_items;
                     total_used += *uses;
                     total_produced += *prod;
-                    
+
                     row.extend(element_vec![
                         scale_field("Net required", uses - prod),
                         scale_field("Cost", cost_items),
@@ -615,17 +2191,147 @@ impl App {
                 row
             }));
 
+            let mut fuels_rows = vec![
+                element_vec![
+                    title_text(TitleLevel::SubSectionTitle, "Fuels"),
+                    text("Smelts per item")
+                ],
+                element_vec![Space::new(Shrink, Shrink), widther()],
+                element_vec![horizontal_rule(SPACE), horizontal_rule(SPACE)],
+            ];
+            fuels_rows.extend(self.fuels.iter().map(|(item, smelts)| {
+                element_vec![
+                    item.displayer(),
+                    ParsedInput::new("Smelts per item", smelts)
+                        .on_input(|v| Message::EditFuelSmelts(item.clone(), v))
+                        .style(parsed_input::danger_on_err(text_input::default))
+                ]
+            }));
 
+            let mut tags_rows = vec![
+                element_vec![
+                    title_text(TitleLevel::SubSectionTitle, "Tags"),
+                    text("Members"),
+                    Space::new(Shrink, Shrink)
+                ],
+                element_vec![
+                    text("A tag stands for any of its members. Use its name as an ingredient."),
+                    widther(),
+                    Space::new(Shrink, Shrink)
+                ],
+                element_vec![
+                    horizontal_rule(SPACE),
+                    horizontal_rule(SPACE),
+                    horizontal_rule(SPACE)
+                ],
+            ];
+            tags_rows.extend(self.tags.iter().map(|(tag, members)| {
+                let draft = self.tag_drafts.get(tag).unwrap_or(&EMPTY_ITEM);
+                element_vec![
+                    tag.displayer(),
+                    Column::with_children(
+                        members
+                            .iter()
+                            .enumerate()
+                            .map(|(index, member)| {
+                                row![
+                                    member.displayer(),
+                                    horizontal_space(),
+                                    button(text("Remove")).on_press(Message::EditTag(
+                                        tag.clone(),
+                                        TagEdit::RemoveMember(index)
+                                    ))
+                                ]
+                                .spacing(SPACE)
+                                .align_y(iced::Alignment::Center)
+                                .into()
+                            })
+                            .chain([row![
+                                draft.builder(
+                                    move |m| Message::EditTag(tag.clone(), TagEdit::Draft(m)),
+                                    Some(Message::EditTag(tag.clone(), TagEdit::AddMember))
+                                ),
+                                button(text("Add member"))
+                                    .on_press(Message::EditTag(tag.clone(), TagEdit::AddMember))
+                            ]
+                            .spacing(SPACE)
+                            .align_y(iced::Alignment::Center)
+                            .into()])
+                    )
+                    .spacing(SPACE),
+                    button(text("Delete")).on_press(Message::EditTag(tag.clone(), TagEdit::Delete))
+                ]
+            }));
+            tags_rows.push(element_vec![
+                self.new_tag
+                    .builder(Message::EditNewTag, Some(Message::AddTag)),
+                button(text("Add tag")).on_press(Message::AddTag),
+                Space::new(Shrink, Shrink)
+            ]);
+
+            let mut modifiers_rows = vec![
+                element_vec![
+                    title_text(TitleLevel::SubSectionTitle, "Modifiers"),
+                    text("Name"),
+                    text("Recipe label"),
+                    text("Products ×"),
+                    text("Ingredients ×"),
+                    Space::new(Shrink, Shrink)
+                ],
+                element_vec![
+                    Space::new(Shrink, Shrink),
+                    widther(),
+                    widther(),
+                    widther(),
+                    widther(),
+                    Space::new(Shrink, Shrink)
+                ],
+                element_vec![
+                    horizontal_rule(SPACE),
+                    horizontal_rule(SPACE),
+                    horizontal_rule(SPACE),
+                    horizontal_rule(SPACE),
+                    horizontal_rule(SPACE),
+                    horizontal_rule(SPACE)
+                ],
+            ];
+            modifiers_rows.extend(self.modifiers.iter().enumerate().map(|(index, modifier)| {
+                let edit = move |edit| Message::EditModifier(index, edit);
+                element_vec![
+                    Checkbox::new("", modifier.enabled)
+                        .on_toggle(move |v| edit(ModifierEdit::Toggle(v))),
+                    text_input("Name", &modifier.name)
+                        .on_input(move |v| edit(ModifierEdit::Name(v))),
+                    text_input("All recipes", &modifier.label)
+                        .on_input(move |v| edit(ModifierEdit::Label(v))),
+                    ParsedInput::new("Products ×", &modifier.products)
+                        .on_input(move |v| edit(ModifierEdit::Products(v)))
+                        .style(parsed_input::danger_on_err(text_input::default)),
+                    ParsedInput::new("Ingredients ×", &modifier.ingredients)
+                        .on_input(move |v| edit(ModifierEdit::Ingredients(v)))
+                        .style(parsed_input::danger_on_err(text_input::default)),
+                    button(text("Delete"))
+                        .on_press(edit(ModifierEdit::Delete))
+                        .style(button::danger)
+                ]
+            }));
+            modifiers_rows.push(element_vec![
+                button(text("Add modifier")).on_press(Message::AddModifier)
+            ]);
 
             let mut all_rows = vec![
                 {
                     let mut row = element_vec![
                         title_text(TitleLevel::SubSectionTitle, "All"),
                         text("target"),
-                        text("raw material")
+                        text("raw material"),
+                        text("fuel"),
+                        text("buy price"),
+                        text("max production"),
+                        text("max surplus")
                     ];
                     if self.item_stats.is_some() {
-                        row.extend(element_vec!["Uses", "Produced", "Net production"])
+                        row.extend(element_vec!["Uses", "Crafted", "Bought", "Net production"])
                     }
                     row
                 },
@@ -633,10 +2339,14 @@ impl App {
                     let mut row = element_vec![
                         Space::new(Shrink, Shrink),
                         Space::new(Shrink, Shrink),
-                        Space::new(Shrink, Shrink)
+                        Space::new(Shrink, Shrink),
+                        Space::new(Shrink, Shrink),
+                        widther(),
+                        widther(),
+                        widther()
                     ];
                     if self.item_stats.is_some() {
-                        row.extend([widther(), widther(), widther()])
+                        row.extend([widther(), widther(), widther(), widther()])
                     }
                     row
                 },
@@ -645,12 +2355,17 @@ impl App {
                         horizontal_rule(SPACE),
                         horizontal_rule(SPACE),
                         horizontal_rule(SPACE),
+                        horizontal_rule(SPACE),
+                        horizontal_rule(SPACE),
+                        horizontal_rule(SPACE),
+                        horizontal_rule(SPACE),
                     ];
                     if self.item_stats.is_some() {
                         row.extend([
                             horizontal_rule(SPACE),
                             horizontal_rule(SPACE),
                             horizontal_rule(SPACE),
+                            horizontal_rule(SPACE),
                         ])
                     }
                     row
@@ -659,6 +2374,7 @@ impl App {
 
             let mut total_uses = 0.;
             let mut total_prod = 0.;
+            let mut total_bought = 0.;
             let mut total_net = 0.;
 
             all_rows.extend(all.iter().map(|(item, (_, target, raw))| {
@@ -667,16 +2383,80 @@ impl App {
                     Checkbox::new("", target.is_some())
                         .on_toggle(|v| Message::ToggleTarget((*item).clone(), v)),
                     Checkbox::new("", raw.is_some())
-                        .on_toggle(|v| Message::ToggleRaw((*item).clone(), v))
+                        .on_toggle(|v| Message::ToggleRaw((*item).clone(), v)),
+                    Checkbox::new("", self.fuels.contains_key(*item))
+                        .on_toggle(|v| Message::ToggleFuel((*item).clone(), v))
                 ];
+                let price = self.buy_prices.get(*item);
+                row.push(
+                    row![
+                        Checkbox::new("", price.is_some())
+                            .on_toggle(|v| Message::ToggleBuy((*item).clone(), v))
+                    ]
+                    .push_maybe(price.map(|price| {
+                        ParsedInput::new("Price", price)
+                            .on_input(|v| Message::EditBuyPrice((*item).clone(), v))
+                            .style(parsed_input::danger_on_err(text_input::default))
+                    }))
+                    .extend(price.iter().flat_map(|_| self.dimensions.iter()).map(|d| {
+                        let (id, item) = (d.id, (*item).clone());
+                        let price = self
+                            .buy_dimension_prices
+                            .get(&item)
+                            .and_then(|prices| prices.get(&id))
+                            .unwrap_or(&ZERO_COST);
+                        ParsedInput::new(&d.name, price)
+                            .on_input(move |v| Message::EditBuyDimensionPrice(item.clone(), id, v))
+                            .style(parsed_input::danger_on_err(text_input::default))
+                            .into()
+                    }))
+                    .spacing(SPACE)
+                    .align_y(iced::Alignment::Center),
+                );
+                let max_production = self.max_production.get(*item);
+                row.push(
+                    row![
+                        Checkbox::new("", max_production.is_some())
+                            .on_toggle(|v| Message::ToggleMaxProduction((*item).clone(), v))
+                    ]
+                    .push_maybe(max_production.map(|max| {
+                        ParsedInput::new("Produced", max)
+                            .on_input(|v| Message::EditMaxProduction((*item).clone(), v))
+                            .style(parsed_input::danger_on_err(text_input::default))
+                    }))
+                    .spacing(SPACE)
+                    .align_y(iced::Alignment::Center),
+                );
+                let max_surplus = self.max_surplus.get(*item);
+                row.push(
+                    row![
+                        Checkbox::new("", max_surplus.is_some())
+                            .on_toggle(|v| Message::ToggleMaxSurplus((*item).clone(), v))
+                    ]
+                    .push_maybe(max_surplus.map(|max| {
+                        ParsedInput::new("Surplus", max)
+                            .on_input(|v| Message::EditMaxSurplus((*item).clone(), v))
+                            .style(parsed_input::danger_on_err(text_input::default))
+                    }))
+                    .spacing(SPACE)
+                    .align_y(iced::Alignment::Center),
+                );
                 if let Some((prod, uses)) = self.item_stats.as_ref().and_then(|tbl| tbl.get(item)) {
+                    let bought = self
+                        .bought
+                        .as_ref()
+                        .and_then(|tbl| tbl.get(*item))
+                        .copied()
+                        .unwrap_or_default();
                     total_uses += *uses;
-                    total_prod += *prod;
+                    total_prod += prod - bought;
+                    total_bought += bought;
                     total_net += prod - uses;
-                    
+
                     row.extend(element_vec![
                         scale_field("Uses", *uses),
-                        scale_field("Produced", *prod),
+                        scale_field("Crafted", prod - bought),
+                        scale_field("Bought", bought),
                         scale_field("Net production", prod - uses),
                     ]);
                 }
@@ -685,26 +2465,352 @@ impl App {
 
             if self.item_stats.is_some() {
                 raws_rows.push(element_vec!(Space::new(Shrink, SPACE)));
-                raws_rows.push(element_vec![
-                    Space::new(Shrink, Shrink),
-                    "Totals:",
-                    scale_field("Required total", total_required),
-                    scale_field("Cost total", total_cost),
-                    scale_field("Used total", total_used),
-                    scale_field("Produced total", total_produced)
-                ]);
-                
+                raws_rows.push({
+                    let mut row = element_vec![Space::new(Shrink, Shrink), "Totals:"];
+                    row.extend(self.dimensions.iter().map(|_| Space::new(Shrink, Shrink)));
+                    row.push(Space::new(Shrink, Shrink));
+                    row.extend(element_vec![
+                        scale_field("Required total", total_required),
+                        scale_field("Cost total", total_cost),
+                        scale_field("Used total", total_used),
+                        scale_field("Produced total", total_produced)
+                    ]);
+                    row
+                });
+
                 all_rows.push(element_vec!(Space::new(Shrink, SPACE)));
                 all_rows.push(element_vec![
+                    Space::new(Shrink, Shrink),
+                    Space::new(Shrink, Shrink),
+                    Space::new(Shrink, Shrink),
+                    Space::new(Shrink, Shrink),
                     Space::new(Shrink, Shrink),
                     Space::new(Shrink, Shrink),
                     "Totals:",
                     scale_field("Uses total", total_uses),
-                    scale_field("Produced total", total_prod),
+                    scale_field("Crafted total", total_prod),
+                    scale_field("Bought total", total_bought),
                     scale_field("Net production total", total_net)
                 ]);
             }
 
+            if let Some(recipe_uses) = self.recipe_uses.as_ref() {
+                // Process cost of the recipes, for the main cost and each dimension id.
+                let process_cost = |id: Option<usize>| -> f64 {
+                    self.recipes
+                        .iter()
+                        .zip(recipe_uses)
+                        .map(|(recipe, uses)| match recipe {
+                            recipe::EditableContent::Builder(_) => 0.,
+                            recipe::EditableContent::Built(recipe) => {
+                                let cost = match id {
+                                    None => recipe.get_cost(),
+                                    Some(id) => recipe.get_dimension_costs().get(&id).copied(),
+                                };
+                                cost.unwrap_or_default() * uses
+                            }
+                        })
+                        .sum()
+                };
+
+                // Label in the "Cost of one" column, value in the "Cost" column.
+                let summary_row = |label: String, value: f64| {
+                    let mut row = element_vec![Space::new(Shrink, Shrink), text(label)];
+                    row.extend(self.dimensions.iter().map(|_| Space::new(Shrink, Shrink)));
+                    row.push(Space::new(Shrink, Shrink));
+                    row.push(Space::new(Shrink, Shrink));
+                    row.push(scale_field("Total", value));
+                    row
+                };
+
+                // Cost of the items bought, for the main cost and each dimension id.
+                let purchase_cost = |id: Option<usize>| -> f64 {
+                    self.bought
+                        .iter()
+                        .flatten()
+                        .map(|(item, bought)| {
+                            let price = match id {
+                                None => self.buy_prices.get(item),
+                                Some(id) => self
+                                    .buy_dimension_prices
+                                    .get(item)
+                                    .and_then(|prices| prices.get(&id)),
+                            };
+                            price.map(|price| ***price).unwrap_or_default() * bought
+                        })
+                        .sum()
+                };
+                let main_total = total_cost + process_cost(None) + purchase_cost(None);
+                let mut weighted_total = main_total * **self.cost_weight;
+
+                raws_rows.push(summary_row("Process cost:".to_string(), process_cost(None)));
+                if !self.buy_prices.is_empty() {
+                    raws_rows.push(summary_row(
+                        "Purchase cost:".to_string(),
+                        purchase_cost(None),
+                    ));
+                }
+                raws_rows.push(summary_row("Total cost:".to_string(), main_total));
+                for (dimension, total) in self.dimensions.iter().zip(dimension_totals) {
+                    let total = total
+                        + process_cost(Some(dimension.id))
+                        + purchase_cost(Some(dimension.id));
+                    weighted_total += total * **dimension.weight;
+                    raws_rows.push(summary_row(format!("Total {}:", dimension.name), total));
+                }
+                if !self.dimensions.is_empty() {
+                    raws_rows.push(summary_row("Weighted total:".to_string(), weighted_total));
+                }
+            }
+
+            let (x, y) = self.pareto_axes;
+            let choices: Vec<AxisChoice> = Some(None)
+                .into_iter()
+                .chain(self.dimensions.iter().map(|d| Some(d.id)))
+                .map(|id| AxisChoice {
+                    id,
+                    name: self.dimension_name(id),
+                })
+                .collect();
+            let axis_pick = |is_y: bool, id: Option<usize>| {
+                pick_list(
+                    choices.clone(),
+                    Some(AxisChoice {
+                        id,
+                        name: self.dimension_name(id),
+                    }),
+                    move |choice: AxisChoice| Message::SetParetoAxis(is_y, choice.id),
+                )
+            };
+            let mut pareto_col = column![
+                title_text(TitleLevel::SubSectionTitle, "Pareto frontier"),
+                row![
+                    text("X axis"),
+                    axis_pick(false, x),
+                    text("Y axis"),
+                    axis_pick(true, y)
+                ]
+                .spacing(SPACE)
+                .align_y(iced::Alignment::Center),
+                row![
+                    Checkbox::new(
+                        "Epsilon constraints",
+                        self.pareto_method == ParetoMethod::Epsilon
+                    )
+                    .on_toggle(Message::ToggleParetoEpsilon),
+                    text("Steps"),
+                    TypedInput::new("Steps", &self.pareto_steps).on_input(Message::EditParetoSteps),
+                    button(text("Compute frontier")).on_press_maybe(
+                        (can_compute
+                            && self.solving.is_none()
+                            && x != y
+                            && self.goal == Goal::MinimizeCost)
+                            .then_some(Message::ComputePareto),
+                    )
+                ]
+                .spacing(SPACE)
+                .align_y(iced::Alignment::Center)
+            ]
+            .spacing(SPACE);
+
+            if let Some((plans, loaded)) = self.pareto.as_ref() {
+                const CHART_WIDTH: f32 = 400.;
+                const CHART_HEIGHT: f32 = 250.;
+                const DOT: f32 = SPACE as f32;
+
+                let range = |dimension: Option<usize>| {
+                    plans
+                        .iter()
+                        .map(|plan| plan.costs.get(dimension))
+                        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+                            (min.min(v), max.max(v))
+                        })
+                };
+                let ((x_min, x_max), (y_min, y_max)) = (range(x), range(y));
+                // Position of a value along an axis, in pixels.
+                let position = |v: f64, min: f64, max: f64, length: f32| {
+                    if max > min {
+                        ((v - min) / (max - min)) as f32 * length
+                    } else {
+                        length / 2.
+                    }
+                };
+
+                let points = plans.iter().enumerate().map(|(index, plan)| {
+                    let left = position(plan.costs.get(x), x_min, x_max, CHART_WIDTH);
+                    let top =
+                        CHART_HEIGHT - position(plan.costs.get(y), y_min, y_max, CHART_HEIGHT);
+                    container(
+                        button(Space::new(DOT, DOT))
+                            .padding(0)
+                            .on_press(Message::LoadParetoPoint(index))
+                            .style(if *loaded == Some(index) {
+                                button::success
+                            } else {
+                                button::primary
+                            }),
+                    )
+                    .padding(Padding {
+                        top,
+                        right: 0.,
+                        bottom: 0.,
+                        left,
+                    })
+                    .into()
+                });
+                let chart = Stack::with_children(points)
+                    .width(CHART_WIDTH + DOT)
+                    .height(CHART_HEIGHT + DOT);
+
+                pareto_col = pareto_col
+                    .push(text!(
+                        "{} (vertical): {} to {}",
+                        self.dimension_name(y),
+                        DisplayFloat::new(y_min),
+                        DisplayFloat::new(y_max)
+                    ))
+                    .push(text!(
+                        "{} (horizontal): {} to {}",
+                        self.dimension_name(x),
+                        DisplayFloat::new(x_min),
+                        DisplayFloat::new(x_max)
+                    ))
+                    .push(contoured(chart, |theme: &iced::Theme| theme.palette().text))
+                    .push(text(match loaded.and_then(|index| plans.get(index)) {
+                        Some(plan) => format!(
+                            "Loaded plan: {} {}, {} {}",
+                            DisplayFloat::new(plan.costs.get(x)),
+                            self.dimension_name(x),
+                            DisplayFloat::new(plan.costs.get(y)),
+                            self.dimension_name(y)
+                        ),
+                        None => "Click a point to load its plan.".to_string(),
+                    }));
+            }
+
+            // The price list does not need targets.
+            let can_price = self.error.is_none()
+                && Checks {
+                    targets: true,
+                    ..checks
+                }
+                .all();
+            let mut prices_rows = vec![element_vec![
+                title_text(TitleLevel::SubSectionTitle, "Price list"),
+                button(text("Compute price list")).on_press_maybe(
+                    (can_price && self.solving.is_none()).then_some(Message::ComputePriceList)
+                ),
+                button(text("Export")).on_press_maybe(
+                    self.price_list
+                        .is_some()
+                        .then_some(Message::ExportPriceList)
+                )
+            ]];
+            if let Some(price_list) = self.price_list.as_ref() {
+                let dimensions: Vec<Option<usize>> = Some(None)
+                    .into_iter()
+                    .chain(self.dimensions.iter().map(|d| Some(d.id)))
+                    .collect();
+                let weights = self.weights();
+                prices_rows.push({
+                    let mut row = element_vec![Space::new(Shrink, Shrink)];
+                    row.extend(
+                        dimensions
+                            .iter()
+                            .map(|dimension| text(self.dimension_name(*dimension))),
+                    );
+                    if !self.dimensions.is_empty() {
+                        row.push(text("Weighted cost"))
+                    }
+                    row
+                });
+                prices_rows.push({
+                    let mut row = element_vec![horizontal_rule(SPACE)];
+                    row.extend(dimensions.iter().map(|_| horizontal_rule(SPACE)));
+                    if !self.dimensions.is_empty() {
+                        row.push(horizontal_rule(SPACE))
+                    }
+                    row
+                });
+                prices_rows.extend(price_list.iter().map(|(item, costs)| {
+                    let mut row = element_vec![item.displayer()];
+                    match costs {
+                        Ok(costs) => {
+                            row.extend(dimensions.iter().map(|dimension| {
+                                text!("{}", DisplayFloat::new(costs.get(*dimension)))
+                            }));
+                            if !self.dimensions.is_empty() {
+                                row.push(text!("{}", DisplayFloat::new(costs.weighted(&weights))))
+                            }
+                        }
+                        Err(err) => row.push(text(err)),
+                    }
+                    row
+                }));
+            }
+
+            let recipes_used = self
+                .recipe_uses
+                .as_ref()
+                .map(|uses| uses.iter().filter(|uses| **uses > 1e-6).count());
+            let lines_col = column![
+                title_text(TitleLevel::SubSectionTitle, "Production lines"),
+                row![
+                    Checkbox::new("At most", self.max_recipes.is_some())
+                        .on_toggle(Message::ToggleMaxRecipes)
+                ]
+                .push_maybe(self.max_recipes.map(|max| {
+                    TypedInput::new("Recipes", &max).on_input(Message::EditMaxRecipes)
+                }))
+                .push(text("different recipes"))
+                .spacing(SPACE)
+                .align_y(iced::Alignment::Center),
+                row![
+                    Checkbox::new("Cost per different recipe", self.recipe_penalty.is_some())
+                        .on_toggle(Message::ToggleRecipePenalty)
+                ]
+                .push_maybe(self.recipe_penalty.as_ref().map(|penalty| {
+                    ParsedInput::new("Cost", penalty)
+                        .on_input(Message::EditRecipePenalty)
+                        .style(parsed_input::danger_on_err(text_input::default))
+                }))
+                .spacing(SPACE)
+                .align_y(iced::Alignment::Center)
+            ]
+            .push_maybe(recipes_used.map(|n| text!("Different recipes used: {n}")))
+            .spacing(SPACE);
+
+            let mut rules_col = column![
+                title_text(TitleLevel::SubSectionTitle, "Constraints"),
+                text(
+                    "Compare sums of uses(recipe label), produced(item) and used(item) \
+                     with ≤, ≥ or =. For example: produced(glass) ≥ 2 × produced(glass pane)"
+                )
+            ]
+            .spacing(SPACE);
+            for (index, (rule, parsed)) in self.rules.iter().enumerate() {
+                let error = parsed.as_ref().err();
+                let invalid = error.is_some();
+                rules_col = rules_col
+                    .push(
+                        row![
+                            text_input("Constraint", rule)
+                                .on_input(move |v| Message::EditRule(index, v))
+                                .style(danger_if(invalid)),
+                            button(text("Delete"))
+                                .on_press(Message::DeleteRule(index))
+                                .style(button::danger)
+                        ]
+                        .spacing(SPACE),
+                    )
+                    .push_maybe(error.map(|err| text(err.to_string()).style(text::danger)));
+            }
+            rules_col = rules_col.push(button(text("Add constraint")).on_press(Message::AddRule));
+
+            let dimensions_elt = Grid::with_rows(dimensions_rows)
+                .column_spacing(SPACE)
+                .main_axis(grid::Axis::Vertical)
+                .width(Shrink);
             let raws_elt = Grid::with_rows(raws_rows)
                 .column_spacing(SPACE)
                 .main_axis(grid::Axis::Vertical)
@@ -713,15 +2819,39 @@ impl App {
                 .column_spacing(SPACE)
                 .main_axis(grid::Axis::Vertical)
                 .width(Shrink);
+            let fuels_elt = Grid::with_rows(fuels_rows)
+                .column_spacing(SPACE)
+                .main_axis(grid::Axis::Vertical)
+                .width(Shrink);
+            let tags_elt = Grid::with_rows(tags_rows)
+                .column_spacing(SPACE)
+                .main_axis(grid::Axis::Vertical)
+                .width(Shrink);
+            let modifiers_elt = Grid::with_rows(modifiers_rows)
+                .column_spacing(SPACE)
+                .main_axis(grid::Axis::Vertical)
+                .width(Shrink);
             let all_elt = Grid::with_rows(all_rows)
                 .column_spacing(SPACE)
                 .main_axis(grid::Axis::Vertical)
                 .width(Shrink);
+            let prices_elt = Grid::with_rows(prices_rows)
+                .column_spacing(SPACE)
+                .main_axis(grid::Axis::Vertical)
+                .width(Shrink);
 
             column![
                 contoured(targets_elt, |theme: &iced::Theme| theme.palette().text),
+                contoured(dimensions_elt, |theme: &iced::Theme| theme.palette().text),
+                contoured(pareto_col, |theme: &iced::Theme| theme.palette().text),
+                contoured(lines_col, |theme: &iced::Theme| theme.palette().text),
+                contoured(rules_col, |theme: &iced::Theme| theme.palette().text),
                 contoured(raws_elt, |theme: &iced::Theme| theme.palette().text),
-                contoured(all_elt, |theme: &iced::Theme| theme.palette().text)
+                contoured(fuels_elt, |theme: &iced::Theme| theme.palette().text),
+                contoured(tags_elt, |theme: &iced::Theme| theme.palette().text),
+                contoured(modifiers_elt, |theme: &iced::Theme| theme.palette().text),
+                contoured(all_elt, |theme: &iced::Theme| theme.palette().text),
+                contoured(prices_elt, |theme: &iced::Theme| theme.palette().text)
             ]
             .spacing(SPACE)
         })
@@ -747,25 +2877,37 @@ impl App {
 
         // Compute button
 
-        let all_recipes_ok = self.recipes.len() > 0
-            && self.recipes.iter().all(|recipe| match recipe {
-                recipe::EditableContent::Builder(_) => false,
-                recipe::EditableContent::Built(_) => true,
-            });
-
-        let compute_button = button(title_text(TitleLevel::SectionTitle, "Compute"))
-            .width(Fill)
-            .on_press_maybe(
-                if self.error.is_none() && all_recipes_ok && all_targets_ok && all_raws_ok {
-                    Some(Message::Compute)
+        let compute_button: Element<'_, Message> = if let Some(worker) = self.solving.as_ref() {
+            // A computation outdated by an edit stops before its next optimization, so it may take a while.
+            let cancelled = worker.cancel.load(Ordering::Relaxed);
+            row![
+                Spinner::new()
+                    .width(Fixed(SPACE as f32 * 3.))
+                    .height(Fixed(SPACE as f32 * 3.)),
+                text(if cancelled {
+                    "Stopping..."
                 } else {
-                    None
-                },
-            );
-        let compute_button = if self.error.is_some() {
-            compute_button.style(button::danger)
+                    "Solving..."
+                }),
+                horizontal_space(),
+            ]
+            .push_maybe((!cancelled).then(|| {
+                button(text("Cancel"))
+                    .on_press(Message::CancelCompute)
+                    .style(button::danger)
+            }))
+            .spacing(SPACE)
+            .align_y(iced::Alignment::Center)
+            .into()
         } else {
-            compute_button
+            let compute_button = button(title_text(TitleLevel::SectionTitle, "Compute"))
+                .width(Fill)
+                .on_press_maybe(can_compute.then_some(Message::Compute));
+            if self.error.is_some() {
+                compute_button.style(button::danger).into()
+            } else {
+                compute_button.into()
+            }
         };
 
         // Menu bar
@@ -787,7 +2929,32 @@ impl App {
             }),
             horizontal_rule(SPACE),
             Container::new(
-                column![content, compute_button]
+                column![
+                    content,
+                    row![
+                        compute_button,
+                        Checkbox::new("Live", self.live).on_toggle(Message::ToggleLive),
+                        button(text("Export model…"))
+                            .on_press_maybe(can_compute.then_some(Message::ExportModel))
+                    ]
+                    .spacing(SPACE)
+                    .align_y(iced::Alignment::Center)
+                ]
+                    .push_maybe((self.goal == Goal::MaximizeOutput).then(|| {
+                        text(
+                            "Export model… only writes the program of the highest priority level. \
+                            Each next level is solved after it, keeping the output of the previous ones.",
+                        )
+                    }))
+                    .push_maybe(
+                        self.pruned
+                            .filter(|(recipes, items)| *recipes > 0 || *items > 0)
+                            .map(|(recipes, items)| {
+                                text!(
+                                    "{recipes} recipes and {items} items cannot help to meet the targets, so they were left out."
+                                )
+                            }),
+                    )
                     .push_maybe(
                         self.save_error
                             .as_ref()
@@ -804,7 +2971,8 @@ impl App {
         ];
 
         // Add save popup or build element
-        let elt = if self.save_popup.is_some() {
+
+        if self.save_popup.is_some() {
             let popup = center(container(
                 Grid::new().push_row(
                     [title_text(TitleLevel::SectionTitle, "Save changes before close?")])
@@ -833,29 +3001,30 @@ impl App {
                 .into()
         } else {
             main_window.into()
-        };
-
-        elt //.explain(iced::Color::BLACK)
+        } //.explain(iced::Color::BLACK)
     }
 
     fn subscription(&self) -> Subscription<Message> {
         let tab = keyboard::on_key_press(|key, modifiers| match key {
-            keyboard::Key::Named(named) => match named {
-                keyboard::key::Named::Tab => {
-                    if modifiers.shift() {
-                        Some(Message::FocusPrevious)
-                    } else {
-                        Some(Message::FocusNext)
-                    }
+            keyboard::Key::Named(keyboard::key::Named::Tab) => {
+                if modifiers.shift() {
+                    Some(Message::FocusPrevious)
+                } else {
+                    Some(Message::FocusNext)
                 }
-                _ => None,
-            },
+            }
             _ => None,
         });
 
-        let close_request = window::close_requests().map(|id| Message::CloseRequest(id));
+        let close_request = window::close_requests().map(Message::CloseRequest);
+
+        // Only runs while a live computation waits for the edits to stop.
+        let live = match self.live_deadline {
+            Some(_) => Subscription::run(live_ticks),
+            None => Subscription::none(),
+        };
 
-        Subscription::batch([tab, close_request])
+        Subscription::batch([tab, close_request, live])
     }
 
     // fn into_save(self) -> AppSave {
@@ -892,6 +3061,117 @@ impl App {
                     )
                 })
                 .collect(),
+            fuels: self
+                .fuels
+                .iter()
+                .map(|(k, c)| (k.clone(), c.clone().into_value()))
+                .collect(),
+            tags: self.tags.clone(),
+            modifiers: self
+                .modifiers
+                .iter()
+                .map(|m| Modifier {
+                    name: m.name.clone(),
+                    label: m.label.clone(),
+                    enabled: m.enabled,
+                    products: m.products.clone().into_value(),
+                    ingredients: m.ingredients.clone().into_value(),
+                })
+                .collect(),
+            cost_weight: self.cost_weight.clone().into_value(),
+            dimensions: self
+                .dimensions
+                .iter()
+                .map(|d| CostDimension {
+                    id: d.id,
+                    name: d.name.clone(),
+                    weight: d.weight.clone().into_value(),
+                })
+                .collect(),
+            next_dimension_id: self.next_dimension_id,
+            raw_dimension_costs: self
+                .raw_dimension_costs
+                .iter()
+                .map(|(item, costs)| {
+                    (
+                        item.clone(),
+                        costs
+                            .iter()
+                            .map(|(id, c)| (*id, c.clone().into_value()))
+                            .collect(),
+                    )
+                })
+                .collect(),
+            raw_limits: self
+                .raw_limits
+                .iter()
+                .map(|(item, c)| (item.clone(), c.clone().into_value()))
+                .collect(),
+            max_production: self
+                .max_production
+                .iter()
+                .map(|(item, c)| (item.clone(), c.clone().into_value()))
+                .collect(),
+            max_surplus: self
+                .max_surplus
+                .iter()
+                .map(|(item, c)| (item.clone(), c.clone().into_value()))
+                .collect(),
+            goal: self.goal,
+            target_priorities: self.target_priorities.clone(),
+            buy_prices: self
+                .buy_prices
+                .iter()
+                .map(|(item, c)| (item.clone(), c.clone().into_value()))
+                .collect(),
+            bought: self.bought.clone(),
+            max_recipes: self.max_recipes,
+            recipe_penalty: self.recipe_penalty.clone().map(|c| c.into_value()),
+            rules: self.rules.iter().map(|(text, _)| text.clone()).collect(),
+            live: self.live,
+            burnt: self.burnt.clone(),
+            tag_uses: self.tag_uses.clone(),
+            buy_dimension_prices: self
+                .buy_dimension_prices
+                .iter()
+                .map(|(item, prices)| {
+                    (
+                        item.clone(),
+                        prices
+                            .iter()
+                            .map(|(id, c)| (*id, c.clone().into_value()))
+                            .collect(),
+                    )
+                })
+                .collect(),
+            uses_locks: self
+                .uses_locks
+                .iter()
+                .map(|lock| {
+                    lock.as_ref().map(|lock| UsesLock {
+                        range: lock.range,
+                        min: lock.min.clone().into_value(),
+                        max: lock.max.clone().into_value(),
+                    })
+                })
+                .collect(),
+            ratio_groups: self
+                .ratio_groups
+                .iter()
+                .map(|group| RatioGroup {
+                    priority: group.priority,
+                    name: group.name.clone(),
+                    amount: group.amount.clone().into_value(),
+                    members: group
+                        .members
+                        .iter()
+                        .map(|(item, ratio)| (item.clone(), ratio.clone().into_value()))
+                        .collect(),
+                })
+                .collect(),
+            pareto_axes: self.pareto_axes,
+            pareto_method: self.pareto_method,
+            pareto_steps: self.pareto_steps,
             error: self.error.clone(),
             recipe_uses: self.recipe_uses.clone(),
             item_stats: self.item_stats.clone(),
@@ -920,11 +3200,11 @@ impl App {
             Ok(_) => {
                 self.path = Some(path_buf);
                 self.unsaved_changes = false;
-                return Task::done(Message::SaveDone);
+                Task::done(Message::SaveDone)
             }
             Err(err) => {
                 self.save_error = Some(err);
-                return Task::none();
+                Task::none()
             }
         }
     }
@@ -1006,11 +3286,67 @@ struct AppSave {
     recipe_uses: Option<Vec<f64>>,
     item_stats: Option<HashMap<Item, (f64, f64)>>, // produced used
     scale: TargetAmount,
+    #[serde(default)]
+    fuels: BTreeMap<Item, TargetAmount>, // Smelts per item
+    #[serde(default)]
+    tags: BTreeMap<Item, Vec<Item>>, // Members of each tag
+    #[serde(default)]
+    modifiers: Vec<Modifier<TargetAmount>>,
+    #[serde(default)]
+    cost_weight: TargetAmount,
+    #[serde(default)]
+    dimensions: Vec<CostDimension<TargetAmount>>,
+    #[serde(default)]
+    next_dimension_id: usize,
+    #[serde(default)]
+    raw_dimension_costs: BTreeMap<Item, BTreeMap<usize, TargetAmount>>,
+    #[serde(default)]
+    pareto_axes: (Option<usize>, Option<usize>),
+    #[serde(default)]
+    pareto_method: ParetoMethod,
+    #[serde(default = "default_pareto_steps")]
+    pareto_steps: usize,
+    #[serde(default)]
+    raw_limits: BTreeMap<Item, TargetAmount>,
+    #[serde(default)]
+    goal: Goal,
+    #[serde(default)]
+    ratio_groups: Vec<RatioGroup<TargetAmount, Quantity>>,
+    #[serde(default)]
+    target_priorities: BTreeMap<Item, u32>,
+    #[serde(default)]
+    buy_prices: BTreeMap<Item, TargetAmount>,
+    #[serde(default)]
+    bought: Option<HashMap<Item, f64>>,
+    #[serde(default)]
+    max_recipes: Option<usize>,
+    #[serde(default)]
+    recipe_penalty: Option<TargetAmount>,
+    #[serde(default)]
+    rules: Vec<String>,
+    #[serde(default)]
+    uses_locks: Vec<Option<UsesLock<TargetAmount>>>,
+    #[serde(default)]
+    max_production: BTreeMap<Item, TargetAmount>,
+    #[serde(default)]
+    max_surplus: BTreeMap<Item, TargetAmount>,
+    #[serde(default)]
+    live: bool,
+    #[serde(default)]
+    burnt: Option<HashMap<Item, f64>>,
+    #[serde(default)]
+    tag_uses: Option<HashMap<Item, Vec<(Item, f64)>>>,
+    #[serde(default)]
+    buy_dimension_prices: BTreeMap<Item, BTreeMap<usize, TargetAmount>>,
+}
+
+fn default_pareto_steps() -> usize {
+    10
 }
 
 impl<P: Into<PathBuf>> From<(AppSave, P)> for App {
     fn from((value, path): (AppSave, P)) -> Self {
-        Self {
+        let mut app = Self {
             recipes: value.recipes.into_iter().map(|v| v.into()).collect(),
             known_items: value
                 .known_items
@@ -1020,13 +3356,142 @@ impl<P: Into<PathBuf>> From<(AppSave, P)> for App {
                         k,
                         (
                             i,
-                            a.map(|a| parsed_input::Content::new(a)),
-                            b.map(|b| parsed_input::Content::new(b)),
+                            a.map(parsed_input::Content::new),
+                            b.map(parsed_input::Content::new),
                         ),
                     )
                 })
                 .collect(),
+            fuels: value
+                .fuels
+                .into_iter()
+                .map(|(k, c)| (k, parsed_input::Content::new(c)))
+                .collect(),
+            tags: value.tags,
+            new_tag: Item::default(),
+            tag_drafts: BTreeMap::new(),
+            modifiers: value
+                .modifiers
+                .into_iter()
+                .map(|m| Modifier {
+                    name: m.name,
+                    label: m.label,
+                    enabled: m.enabled,
+                    products: parsed_input::Content::new(m.products),
+                    ingredients: parsed_input::Content::new(m.ingredients),
+                })
+                .collect(),
+            cost_weight: parsed_input::Content::new(value.cost_weight),
+            dimensions: value
+                .dimensions
+                .into_iter()
+                .map(|d| CostDimension {
+                    id: d.id,
+                    name: d.name,
+                    weight: parsed_input::Content::new(d.weight),
+                })
+                .collect(),
+            next_dimension_id: value.next_dimension_id,
+            raw_dimension_costs: value
+                .raw_dimension_costs
+                .into_iter()
+                .map(|(item, costs)| {
+                    (
+                        item,
+                        costs
+                            .into_iter()
+                            .map(|(id, c)| (id, parsed_input::Content::new(c)))
+                            .collect(),
+                    )
+                })
+                .collect(),
+            raw_limits: value
+                .raw_limits
+                .into_iter()
+                .map(|(item, c)| (item, parsed_input::Content::new(c)))
+                .collect(),
+            max_production: value
+                .max_production
+                .into_iter()
+                .map(|(item, c)| (item, parsed_input::Content::new(c)))
+                .collect(),
+            max_surplus: value
+                .max_surplus
+                .into_iter()
+                .map(|(item, c)| (item, parsed_input::Content::new(c)))
+                .collect(),
+            goal: value.goal,
+            target_priorities: value.target_priorities,
+            buy_prices: value
+                .buy_prices
+                .into_iter()
+                .map(|(item, c)| (item, parsed_input::Content::new(c)))
+                .collect(),
+            buy_dimension_prices: value
+                .buy_dimension_prices
+                .into_iter()
+                .map(|(item, prices)| {
+                    (
+                        item,
+                        prices
+                            .into_iter()
+                            .map(|(id, c)| (id, parsed_input::Content::new(c)))
+                            .collect(),
+                    )
+                })
+                .collect(),
+            bought: value.bought,
+            burnt: value.burnt,
+            tag_uses: value.tag_uses,
+            shares: None,
+            expanded_shares: BTreeSet::new(),
+            pruned: None,
+            max_recipes: value.max_recipes,
+            recipe_penalty: value.recipe_penalty.map(parsed_input::Content::new),
+            rules: value
+                .rules
+                .into_iter()
+                .map(|text| {
+                    let rule = text.parse();
+                    (text, rule)
+                })
+                .collect(),
+            uses_locks: value
+                .uses_locks
+                .into_iter()
+                .map(|lock| {
+                    lock.map(|lock| UsesLock {
+                        range: lock.range,
+                        min: parsed_input::Content::new(lock.min),
+                        max: parsed_input::Content::new(lock.max),
+                    })
+                })
+                .collect(),
+            ratio_groups: value
+                .ratio_groups
+                .into_iter()
+                .map(|group| RatioGroup {
+                    priority: group.priority,
+                    name: group.name,
+                    amount: parsed_input::Content::new(group.amount),
+                    members: group
+                        .members
+                        .into_iter()
+                        .map(|(item, ratio)| (item, parsed_input::Content::new(ratio)))
+                        .collect(),
+                })
+                .collect(),
+            pareto_axes: value.pareto_axes,
+            pareto_method: value.pareto_method,
+            pareto_steps: value.pareto_steps.max(1),
+            pareto: None,
+            price_list: None,
+            why_not: None,
             error: value.error,
+            solving: None,
+            generation: 0,
+            live: value.live,
+            live_deadline: None,
             recipe_uses: value.recipe_uses,
             item_stats: value.item_stats,
             scale: value.scale,
@@ -1037,7 +3502,9 @@ impl<P: Into<PathBuf>> From<(AppSave, P)> for App {
             import_error: None,
             save_error: None,
             save_popup: None,
-        }
+        };
+        app.shares = app.saved_shares();
+        app
     }
 }
 
@@ -1046,7 +3513,44 @@ impl Default for App {
         Self {
             recipes: Default::default(),
             known_items: Default::default(),
+            fuels: Default::default(),
+            tags: Default::default(),
+            new_tag: Item::default(),
+            tag_drafts: BTreeMap::new(),
+            modifiers: Default::default(),
+            cost_weight: Default::default(),
+            dimensions: Default::default(),
+            next_dimension_id: 0,
+            raw_dimension_costs: Default::default(),
+            raw_limits: Default::default(),
+            max_production: Default::default(),
+            max_surplus: Default::default(),
+            goal: Goal::default(),
+            target_priorities: BTreeMap::new(),
+            buy_prices: BTreeMap::new(),
+            buy_dimension_prices: BTreeMap::new(),
+            bought: None,
+            burnt: None,
+            tag_uses: None,
+            shares: None,
+            expanded_shares: BTreeSet::new(),
+            pruned: None,
+            max_recipes: None,
+            recipe_penalty: None,
+            rules: Vec::new(),
+            uses_locks: Vec::new(),
+            ratio_groups: Vec::new(),
+            pareto_axes: (None, None),
+            pareto_method: ParetoMethod::default(),
+            pareto_steps: default_pareto_steps(),
+            pareto: None,
+            price_list: None,
+            why_not: None,
             error: Default::default(),
+            solving: None,
+            generation: 0,
+            live: false,
+            live_deadline: None,
             recipe_uses: Default::default(),
             item_stats: Default::default(),
             scale: Default::default(),
@@ -1066,6 +3570,33 @@ impl Default for App {
 //         .add_filter("Craft tree", &[EXTENSION])
 // }
 
+/// Sends [`Message::LiveTick`] every [`LIVE_TICK`] from a single thread, until the stream is dropped.
+fn live_ticks() -> impl Stream<Item = Message> {
+    let (mut sender, receiver) = mpsc::channel(1);
+    thread::spawn(move || {
+        loop {
+            thread::sleep(LIVE_TICK);
+            // A full channel only skips a tick.
+            if sender
+                .try_send(Message::LiveTick)
+                .is_err_and(|err| err.is_disconnected())
+            {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// Quotes a field of a CSV file if needed.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
 fn file_select_win_builder(
     title: impl Into<String>,
     path: impl AsRef<Path>,
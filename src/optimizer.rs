@@ -0,0 +1,1794 @@
+//! Everything to do with building and solving the linear program of a crafting tree.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use good_lp::{
+    Constraint, DualValues, Expression, IntoAffineExpression, ProblemVariables, ResolutionError,
+    Solution, SolutionWithDual, SolverModel, Variable, solvers, variable,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{recipes::Recipe, ui::Item};
+
+pub mod attribution;
+pub mod export;
+pub mod rule;
+use rule::{Reference, Relation, Rule};
+
+/// Most crafts of a single recipe without a maximum number of uses, per item or craft the model asks for and per item
+/// the recipe makes, when the recipes used are counted. A recipe that makes less than one item per craft can be
+/// crafted that many more times.
+const USES_PER_DEMAND: f64 = 1e4;
+
+/// Weight given to the other dimension of a Pareto frontier, so that ties are broken towards optimal plans.
+const TIE_BREAK: f64 = 1e-3;
+
+/// An amount in every cost dimension.
+///
+/// The main dimension has no id, the others are found by id. A missing dimension is 0.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Costs {
+    /// Amount in the main cost dimension.
+    pub main: f64,
+    /// Amounts in the other cost dimensions, by dimension id.
+    pub dimensions: BTreeMap<usize, f64>,
+}
+
+impl Costs {
+    /// Retrieves the amount in a dimension. `None` is the main dimension.
+    pub fn get(&self, dimension: Option<usize>) -> f64 {
+        match dimension {
+            None => self.main,
+            Some(id) => self.dimensions.get(&id).copied().unwrap_or_default(),
+        }
+    }
+
+    /// Sets the amount in a dimension. `None` is the main dimension.
+    pub fn set(&mut self, dimension: Option<usize>, amount: f64) {
+        match dimension {
+            None => self.main = amount,
+            Some(id) => {
+                self.dimensions.insert(id, amount);
+            }
+        }
+    }
+
+    /// Sum of the amounts, each multiplied by the weight of its dimension.
+    pub fn weighted(&self, weights: &Costs) -> f64 {
+        self.main * weights.main
+            + weights
+                .dimensions
+                .iter()
+                .map(|(id, weight)| self.get(Some(*id)) * weight)
+                .sum::<f64>()
+    }
+}
+
+/// What the optimizer looks for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Goal {
+    /// Meet the targets at the minimum weighted cost.
+    #[default]
+    MinimizeCost,
+    /// Maximize the weighted sum of the targets produced, within the availability of the raw materials.
+    MaximizeOutput,
+}
+
+/// What the optimizer does with an item.
+#[derive(Debug, Clone)]
+pub enum ItemRole {
+    /// Must not be net consumed.
+    Intermediate,
+    /// Must be net produced in at least this amount.
+    ///
+    /// When maximizing the output, this is instead the weight of the item in the output of its priority level.
+    Target {
+        /// Amount to produce, or weight in the output.
+        amount: f64,
+        /// Priority level of the target. Only used when maximizing the output.
+        priority: u32,
+    },
+    /// Can be net consumed, at this cost per item, and up to the available amount if any. A surplus earns nothing.
+    Raw {
+        /// Cost of one item.
+        costs: Costs,
+        /// Maximum amount net consumed.
+        available: Option<f64>,
+    },
+}
+
+/// Items produced together in a fixed ratio, by complete sets.
+#[derive(Debug, Clone)]
+pub struct RatioGroup {
+    /// Items of a set, with their number per set. At least one of them has to be positive.
+    ///
+    /// The sets come on top of what a member is needed for elsewhere, as a target or in another group.
+    pub members: Vec<(Item, f64)>,
+    /// Number of sets to produce.
+    ///
+    /// When maximizing the output, this is instead the weight of one set in the output of its priority level.
+    pub amount: f64,
+    /// Priority level of the group. Only used when maximizing the output.
+    pub priority: u32,
+}
+
+/// A [`Recipe`] of a [`Model`], with the multipliers of the modifiers that apply to it.
+#[derive(Debug, Clone)]
+pub struct ModelRecipe {
+    /// The recipe.
+    pub recipe: Recipe<Item>,
+    /// Multiplier of the quantities produced.
+    pub products_mult: f64,
+    /// Multiplier of the quantities consumed.
+    pub ingredients_mult: f64,
+    /// Minimum number of uses of the recipe.
+    pub min_uses: f64,
+    /// Maximum number of uses of the recipe, if any.
+    pub max_uses: Option<f64>,
+}
+
+/// Everything the optimizer needs to know about a crafting tree.
+#[derive(Debug, Clone, Default)]
+pub struct Model {
+    /// The recipes. The uses of a [`Plan`] are in the same order.
+    pub recipes: Vec<ModelRecipe>,
+    /// Every item that appears in the recipes, fuels or tags.
+    pub items: BTreeMap<Item, ItemRole>,
+    /// Fuel items, with the number of smelting operations per item.
+    pub fuels: BTreeMap<Item, f64>,
+    /// Members of each tag.
+    pub tags: BTreeMap<Item, Vec<Item>>,
+    /// Maximum amount produced of some items, the amount bought included.
+    pub max_production: BTreeMap<Item, f64>,
+    /// Maximum net production of some items, what is left once the uses are subtracted.
+    pub max_surplus: BTreeMap<Item, f64>,
+    /// Targets that are sets of items in a fixed ratio.
+    pub ratio_groups: Vec<RatioGroup>,
+    /// Items that can be bought, with the cost of one item in each dimension. They are bought when it is cheaper than
+    /// crafting them.
+    ///
+    /// When maximizing the output, nothing is bought.
+    pub prices: BTreeMap<Item, Costs>,
+    /// Ids of the cost dimensions other than the main one.
+    pub dimensions: Vec<usize>,
+    /// Maximum number of different recipes used.
+    pub max_recipes: Option<usize>,
+    /// Cost added to the weighted cost for each different recipe used. Ignored when maximizing the output.
+    pub recipe_penalty: Option<f64>,
+    /// Linear constraints written by the user.
+    pub rules: Vec<Rule>,
+    /// What the optimizer looks for.
+    pub goal: Goal,
+    /// Set from another thread to stop a computation that runs several optimizations, before the next one starts.
+    /// It then fails with [`SolveError::Cancelled`]. An optimization that already started is not interrupted, so a
+    /// computation made of a single one always finishes.
+    pub cancel: Arc<AtomicBool>,
+}
+
+/// The result of an optimization.
+#[derive(Debug, Clone, Default)]
+pub struct Plan {
+    /// Number of times each recipe is crafted.
+    pub recipe_uses: Vec<f64>,
+    /// Amount produced and used of each item. The amount produced includes the amount bought.
+    pub item_stats: HashMap<Item, (f64, f64)>,
+    /// Amount bought of each item that can be bought.
+    pub bought: HashMap<Item, f64>,
+    /// Amount burnt of each fuel.
+    pub burnt: HashMap<Item, f64>,
+    /// Amount of each member used as each tag.
+    pub tag_uses: HashMap<Item, Vec<(Item, f64)>>,
+    /// Total cost of the plan in each dimension, raw materials and process costs included.
+    pub costs: Costs,
+    /// Weighted sum of the targets produced, by priority level. Only meaningful when maximizing the output.
+    pub outputs: BTreeMap<u32, f64>,
+    /// Number of recipes left out of the optimization, as they cannot help to meet the targets. They are not used.
+    pub pruned_recipes: usize,
+    /// Number of items left out of the optimization, as they cannot help to meet the targets.
+    pub pruned_items: usize,
+}
+
+/// Why a recipe is not used in the cheapest plan. All the costs are weighted.
+#[derive(Debug, Clone, Default)]
+pub struct WhyNot {
+    /// How much cheaper one craft would need to be for the recipe to enter the plan. 0 or less if it can already.
+    pub reduced_cost: f64,
+    /// Cost of one craft: its process cost, ingredients and fuel at their marginal cost.
+    pub craft_cost: f64,
+    /// Value of the products of one craft, at their marginal cost.
+    pub products_value: f64,
+    /// Marginal cost of the items of the recipe: how much one more of them would cost to the plan.
+    pub item_costs: BTreeMap<Item, f64>,
+    /// Marginal cost of one smelting operation.
+    pub smelt_cost: f64,
+    /// Cost of one craft from the other constraints the recipe is in: rules, and production and surplus limits.
+    pub constraints_cost: f64,
+    /// Indices of the recipes of the plan that make the products of this recipe.
+    pub routes: Vec<usize>,
+    /// Uses of the recipe in the plan, in case it is used after all.
+    pub uses: f64,
+    /// The recipe makes nothing the targets need, so it is left out of the optimization. Nothing else is filled in.
+    pub pruned: bool,
+}
+
+/// How the points of a Pareto frontier are found.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ParetoMethod {
+    /// Minimize a weighted sum of the two dimensions, for weights evenly spread between them.
+    #[default]
+    WeightSweep,
+    /// Minimize the first dimension with the second bounded, for bounds evenly spread along the frontier.
+    Epsilon,
+}
+
+/// Error of an optimization.
+#[derive(Debug, Clone)]
+pub enum SolveError {
+    /// An item of a recipe, fuel or tag is missing from the items of the model.
+    MissingItem(Item),
+    /// A recipe has a fuel slot, but there is no fuel.
+    NoFuel,
+    /// The output is maximized, but this raw material has no availability limit.
+    Unlimited(Item),
+    /// A rule refers to a recipe label or an item that does not exist.
+    UnknownReference(Reference),
+    /// The solver failed.
+    Solve(ResolutionError),
+    /// The solver stopped before finding the optimum. Holds the plan it found, and the status.
+    NotOptimal(Box<Plan>, String),
+    /// There is no recipe at this index.
+    MissingRecipe(usize),
+    /// Only meaningful when minimizing the cost.
+    CostOnly,
+    /// The ratio group at this index has no member with a positive ratio.
+    EmptyRatioGroup(usize),
+    /// The recipe at this index reached the number of uses it was limited to, as the recipes used are counted.
+    UsesLimit(usize, f64),
+    /// The computation was cancelled.
+    Cancelled,
+}
+
+impl Display for SolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolveError::MissingItem(item) => write!(
+                f,
+                "Internal error: no expression found for item {}",
+                item.get_name()
+            ),
+            SolveError::NoFuel => write!(f, "A recipe has a fuel slot, but no fuel is defined."),
+            SolveError::Unlimited(item) => write!(
+                f,
+                "{} has no availability limit, so the output can be as large as wanted.",
+                item.get_name()
+            ),
+            SolveError::UnknownReference(reference) => {
+                write!(
+                    f,
+                    "A constraint refers to {reference}, which does not exist."
+                )
+            }
+            SolveError::Solve(err) => write!(f, "Could not solve: {err}"),
+            SolveError::NotOptimal(_, status) => write!(f, "Solution is not optimal. {status}"),
+            SolveError::MissingRecipe(index) => {
+                write!(f, "Internal error: no recipe at index {index}")
+            }
+            SolveError::CostOnly => write!(f, "Only available when minimizing the cost."),
+            SolveError::EmptyRatioGroup(index) => {
+                write!(
+                    f,
+                    "Ratio group {} has no member with a positive ratio.",
+                    index + 1
+                )
+            }
+            SolveError::UsesLimit(index, limit) => write!(
+                f,
+                "Recipe {} is used {limit} times, the most allowed while counting the recipes used. Lock its \
+                maximum number of uses to raise that.",
+                index + 1
+            ),
+            SolveError::Cancelled => write!(f, "Cancelled."),
+        }
+    }
+}
+
+/// The linear program of a [`Model`], before the objective is chosen.
+struct Program {
+    variables: Variables,
+    active: Vec<Variable>, // Binary variables, 1 if the recipe is used. Empty if the recipes used are not counted.
+    constraints: Vec<Row>,
+    balances: HashMap<Item, usize>, // Index of the constraint on the net production of each item, if any
+    fuel: Option<usize>,            // Index of the fuel constraint, if any
+    expressions: Expressions,
+}
+
+/// The variables of a [`Program`], each named after what it stands for, with their bounds so the program can be
+/// exported.
+#[derive(Default)]
+struct Variables {
+    problem: ProblemVariables,
+    columns: Vec<(Variable, Column)>,
+}
+
+/// A variable of a [`Program`].
+struct Column {
+    name: String,
+    min: f64,
+    max: Option<f64>,
+    binary: bool,
+}
+
+impl Variables {
+    /// Adds a continuous variable between `min` and `max`.
+    fn add(&mut self, name: String, min: f64, max: Option<f64>) -> Variable {
+        let definition = variable().name(name.clone()).min(min);
+        let variable = self.problem.add(match max {
+            Some(max) => definition.max(max),
+            None => definition,
+        });
+        self.columns.push((
+            variable,
+            Column {
+                name,
+                min,
+                max,
+                binary: false,
+            },
+        ));
+        variable
+    }
+
+    /// Adds a variable that is either 0 or 1.
+    fn add_binary(&mut self, name: String) -> Variable {
+        let variable = self.problem.add(variable().binary().name(name.clone()));
+        self.columns.push((
+            variable,
+            Column {
+                name,
+                min: 0.,
+                max: Some(1.),
+                binary: true,
+            },
+        ));
+        variable
+    }
+}
+
+/// A constraint of a [`Program`]: an expression of the variables compared to a constant.
+struct Row {
+    name: String,
+    expression: Expression, // No constant
+    relation: Relation,
+    constant: f64,
+}
+
+impl Row {
+    /// Creates the constraint `lhs relation rhs`. The constants are moved to the right side.
+    fn new(
+        name: impl Into<String>,
+        lhs: impl Into<Expression>,
+        relation: Relation,
+        rhs: impl Into<Expression>,
+    ) -> Self {
+        let expression = lhs.into() - rhs.into();
+        let constant = 0. - expression.constant(); // Not -0.
+        Self {
+            name: name.into(),
+            expression: expression + constant,
+            relation,
+            constant,
+        }
+    }
+
+    /// Builds the constraint given to the solver.
+    fn constraint(&self) -> Constraint {
+        let expression = self.expression.clone();
+        match self.relation {
+            Relation::LessOrEqual => expression.leq(self.constant),
+            Relation::GreaterOrEqual => expression.geq(self.constant),
+            Relation::Equal => expression.eq(self.constant),
+        }
+        .set_name(self.name.clone())
+    }
+}
+
+/// The expressions of a [`Program`] that make up a [`Plan`].
+struct Expressions {
+    variables: Vec<Variable>,
+    recipes: Vec<usize>, // Index of the recipe of each variable, the others are pruned
+    recipe_count: usize,
+    pruned_items: Vec<Item>,
+    item_expressions: HashMap<Item, (Expression, Expression)>, // prod uses
+    costs: Vec<(Option<usize>, Expression)>,                   // Cost expression of each dimension
+    outputs: BTreeMap<u32, Expression>, // Weighted sum of the targets of each priority level
+    bought: Vec<(Item, Variable)>,      // Amount bought of each item
+    burnt: Vec<(Item, Variable)>,       // Amount burnt of each fuel
+    tag_uses: Vec<(Item, Item, Variable)>, // Tag, member, amount used as the tag
+    use_limits: Vec<(usize, Variable, f64)>, // Recipe index, its uses, and the limit they were given
+}
+
+impl Expressions {
+    fn plan(self, solution: &impl Solution) -> Result<Plan, SolveError> {
+        let mut plan = Plan {
+            recipe_uses: vec![0.; self.recipe_count],
+            pruned_recipes: self.recipe_count - self.recipes.len(),
+            pruned_items: self.pruned_items.len(),
+            ..Default::default()
+        };
+        for (index, var) in self.recipes.into_iter().zip(self.variables) {
+            plan.recipe_uses[index] = solution.value(var);
+        }
+        for item in self.pruned_items {
+            plan.item_stats.insert(item, (0., 0.));
+        }
+        for (item, (prod_expr, uses_expr)) in self.item_expressions {
+            let prod = solution.eval(prod_expr);
+            let uses = solution.eval(uses_expr);
+            plan.item_stats.insert(item, (prod, uses));
+        }
+        for (dimension, expr) in self.costs {
+            plan.costs.set(dimension, solution.eval(expr));
+        }
+        for (priority, expr) in self.outputs {
+            plan.outputs.insert(priority, solution.eval(expr));
+        }
+        for (item, var) in self.bought {
+            plan.bought.insert(item, solution.value(var));
+        }
+        for (item, var) in self.burnt {
+            plan.burnt.insert(item, solution.value(var));
+        }
+        for (tag, member, var) in self.tag_uses {
+            plan.tag_uses
+                .entry(tag)
+                .or_default()
+                .push((member, solution.value(var)));
+        }
+        for (index, var, limit) in self.use_limits {
+            if solution.value(var) >= limit * (1. - 1e-9) {
+                return Err(SolveError::UsesLimit(index, limit));
+            }
+        }
+
+        match solution.status() {
+            good_lp::SolutionStatus::Optimal => Ok(plan),
+            status => Err(SolveError::NotOptimal(
+                Box::new(plan),
+                format!("{status:?}"),
+            )),
+        }
+    }
+}
+
+/// What a single optimization of a [`Model`] looks for.
+enum Objective<'a> {
+    /// Minimize the weighted cost.
+    Cost(&'a Costs),
+    /// Maximize the output of a priority level.
+    Output(u32),
+}
+
+impl Model {
+    /// Finds the plan that meets the targets with the minimum weighted cost.
+    ///
+    /// When maximizing the output, the cost is ignored. The priority levels are instead maximized one after the other,
+    /// from the highest. Each level keeps its output while the lower ones are maximized.
+    pub fn solve(&self, weights: &Costs) -> Result<Plan, SolveError> {
+        self.solve_bounded(weights, &[])
+    }
+
+    /// Same as [`solve`](Self::solve), with upper bounds on the total cost in some dimensions.
+    pub fn solve_bounded(
+        &self,
+        weights: &Costs,
+        bounds: &[(Option<usize>, f64)],
+    ) -> Result<Plan, SolveError> {
+        match self.goal {
+            Goal::MinimizeCost => self.optimize(Objective::Cost(weights), bounds, &[]),
+            Goal::MaximizeOutput => {
+                let mut kept = Vec::new();
+                let mut plan = None;
+                for priority in self.priorities() {
+                    let level_plan = self.optimize(Objective::Output(priority), bounds, &kept)?;
+                    let output = level_plan
+                        .outputs
+                        .get(&priority)
+                        .copied()
+                        .unwrap_or_default();
+                    kept.push((priority, output - 1e-6 * (1. + output.abs())));
+                    plan = Some(level_plan);
+                }
+                match plan {
+                    Some(plan) => Ok(plan),
+                    None => self.optimize(Objective::Output(0), bounds, &[]),
+                }
+            }
+        }
+    }
+
+    /// Fails if the computation was cancelled.
+    fn check_cancel(&self) -> Result<(), SolveError> {
+        if self.cancel.load(Ordering::Relaxed) {
+            Err(SolveError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Priority levels of the targets and ratio groups, from the highest.
+    fn priorities(&self) -> Vec<u32> {
+        let mut priorities: Vec<u32> = self
+            .items
+            .values()
+            .filter_map(|role| match role {
+                ItemRole::Target { priority, .. } => Some(*priority),
+                _ => None,
+            })
+            .chain(self.ratio_groups.iter().map(|group| group.priority))
+            .collect();
+        priorities.sort_unstable_by(|a, b| b.cmp(a));
+        priorities.dedup();
+        priorities
+    }
+
+    /// Adds upper bounds on the total cost in some dimensions and lower bounds on the output of some priority levels
+    /// to a program, and builds its objective. Returns true if the objective is maximized.
+    fn objective(
+        &self,
+        program: &mut Program,
+        objective: Objective,
+        bounds: &[(Option<usize>, f64)],
+        kept: &[(u32, f64)],
+    ) -> (bool, Expression) {
+        let Program {
+            active,
+            constraints,
+            expressions,
+            ..
+        } = program;
+
+        for (dimension, bound) in bounds {
+            if let Some((_, expr)) = expressions.costs.iter().find(|(d, _)| d == dimension) {
+                let name = match dimension {
+                    None => "cost_bound".to_string(),
+                    Some(id) => format!("cost_{id}_bound"),
+                };
+                constraints.push(Row::new(name, expr.clone(), Relation::LessOrEqual, *bound));
+            }
+        }
+        for (priority, output) in kept {
+            if let Some(expr) = expressions.outputs.get(priority) {
+                constraints.push(Row::new(
+                    format!("priority_{priority}"),
+                    expr.clone(),
+                    Relation::GreaterOrEqual,
+                    *output,
+                ));
+            }
+        }
+
+        match objective {
+            Objective::Cost(weights) => {
+                let mut objective = Expression::default();
+                for (dimension, expr) in expressions.costs.iter() {
+                    let weight = weights.get(*dimension);
+                    if weight != 0. {
+                        objective.add_mul(weight, expr.clone());
+                    }
+                }
+                if let Some(penalty) = self.recipe_penalty {
+                    for var in active.iter() {
+                        objective.add_mul(penalty, *var);
+                    }
+                }
+                (false, objective)
+            }
+            Objective::Output(priority) => (
+                true,
+                expressions
+                    .outputs
+                    .get(&priority)
+                    .cloned()
+                    .unwrap_or_default(),
+            ),
+        }
+    }
+
+    /// Runs one optimization, with upper bounds on the total cost in some dimensions
+    /// and lower bounds on the output of some priority levels.
+    fn optimize(
+        &self,
+        objective: Objective,
+        bounds: &[(Option<usize>, f64)],
+        kept: &[(u32, f64)],
+    ) -> Result<Plan, SolveError> {
+        self.check_cancel()?;
+        let mut program = self.program()?;
+        let (maximise, objective) = self.objective(&mut program, objective, bounds, kept);
+        let Program {
+            variables,
+            active,
+            constraints,
+            expressions,
+            ..
+        } = program;
+
+        let problem = if maximise {
+            variables.problem.maximise(objective)
+        } else {
+            variables.problem.minimise(objective)
+        };
+        let constraints = constraints.iter().map(Row::constraint);
+
+        // Counting the recipes used needs integer variables, that clarabel does not support.
+        if active.is_empty() {
+            let solution = problem
+                .using(solvers::clarabel::clarabel)
+                .with_all(constraints)
+                .solve()
+                .map_err(SolveError::Solve)?;
+            expressions.plan(&solution)
+        } else {
+            let solution = problem
+                .using(solvers::microlp::microlp)
+                .with_all(constraints)
+                .solve()
+                .map_err(SolveError::Solve)?;
+            expressions.plan(&solution)
+        }
+    }
+
+    /// Explains why a recipe is not used in the cheapest plan, with the marginal cost of its items in that plan.
+    ///
+    /// The count and cost of the different recipes used are ignored. The other constraints the recipe is in count in
+    /// its reduced cost.
+    pub fn why_not(&self, weights: &Costs, index: usize) -> Result<WhyNot, SolveError> {
+        if self.goal != Goal::MinimizeCost {
+            return Err(SolveError::CostOnly);
+        }
+        let Some(ModelRecipe {
+            recipe,
+            products_mult,
+            ingredients_mult,
+            ..
+        }) = self.recipes.get(index)
+        else {
+            return Err(SolveError::MissingRecipe(index));
+        };
+
+        self.check_cancel()?;
+        let mut model = self.clone();
+        model.max_recipes = None;
+        model.recipe_penalty = None;
+        let Program {
+            variables,
+            constraints,
+            balances,
+            fuel,
+            expressions,
+            ..
+        } = model.program()?;
+        let Some(position) = expressions.recipes.iter().position(|other| *other == index) else {
+            return Ok(WhyNot {
+                pruned: true,
+                ..Default::default()
+            });
+        };
+        let var = expressions.variables[position];
+
+        let mut objective = Expression::default();
+        for (dimension, expr) in expressions.costs.iter() {
+            let weight = weights.get(*dimension);
+            if weight != 0. {
+                objective.add_mul(weight, expr.clone());
+            }
+        }
+        let mut solver = variables
+            .problem
+            .minimise(objective)
+            .using(solvers::clarabel::clarabel);
+        let references: Vec<_> = constraints
+            .iter()
+            .map(|row| solver.add_constraint(row.constraint()))
+            .collect();
+        let mut solution = solver.solve().map_err(SolveError::Solve)?;
+        let mut recipe_uses = vec![0.; self.recipes.len()];
+        for (other, var) in expressions.recipes.iter().zip(expressions.variables.iter()) {
+            recipe_uses[*other] = solution.value(*var);
+        }
+        let dual = solution.compute_dual();
+
+        // The solver keeps each constraint as `left - right ≤ 0`, or `= 0`, and its dual is at least 0 for an
+        // inequality. A `≥` row is kept as `right - left ≤ 0`. The price of a row is how much one more on its left
+        // side saves, so a craft costs its process cost minus the price of each row times its coefficient there.
+        let price = |row: usize| {
+            let dual = dual.dual(references[row].clone());
+            match constraints[row].relation {
+                Relation::GreaterOrEqual => dual,
+                Relation::LessOrEqual | Relation::Equal => -dual,
+            }
+        };
+
+        // Cost of one more item: how much its constraint holds the plan back. For a raw material, this includes the
+        // cost of the amount taken.
+        let marginal_cost = |item: &Item| {
+            balances
+                .get(item)
+                .map(|row| price(*row))
+                .unwrap_or_default()
+        };
+        let smelt_cost = fuel.map(price).unwrap_or_default();
+
+        let mut why_not = WhyNot {
+            uses: recipe_uses[index],
+            smelt_cost,
+            ..Default::default()
+        };
+        why_not.craft_cost += Costs {
+            main: recipe.get_cost().unwrap_or_default(),
+            dimensions: recipe.get_dimension_costs().clone(),
+        }
+        .weighted(weights);
+        if let Some(smelts) = recipe.get_fuel() {
+            why_not.craft_cost += smelts * smelt_cost;
+        }
+        for (item, qty) in recipe.get_ingredients() {
+            let cost = marginal_cost(item);
+            why_not.item_costs.insert(item.clone(), cost);
+            why_not.craft_cost += cost * qty * ingredients_mult;
+        }
+        for (item, qty, prob) in recipe.get_products() {
+            let cost = marginal_cost(item);
+            why_not.item_costs.insert(item.clone(), cost);
+            why_not.products_value += cost * qty.expected() * prob.get() * products_mult;
+
+            // The recipes used instead.
+            for (other, uses) in recipe_uses.iter().enumerate() {
+                if other != index
+                    && *uses > 1e-6
+                    && !why_not.routes.contains(&other)
+                    && self.recipes[other]
+                        .recipe
+                        .get_products()
+                        .iter()
+                        .any(|(product, _, _)| product == item)
+                {
+                    why_not.routes.push(other);
+                }
+            }
+        }
+        let item_rows: HashSet<usize> = balances.values().copied().chain(fuel).collect();
+        for (row, constraint) in constraints
+            .iter()
+            .enumerate()
+            .filter(|(row, _)| !item_rows.contains(row))
+        {
+            let coef: f64 = (&constraint.expression)
+                .linear_coefficients()
+                .filter(|(other, _)| *other == var)
+                .map(|(_, coef)| coef)
+                .sum();
+            if coef != 0. {
+                why_not.constraints_cost -= price(row) * coef;
+            }
+        }
+        why_not.reduced_cost =
+            why_not.craft_cost - why_not.products_value + why_not.constraints_cost;
+        Ok(why_not)
+    }
+
+    /// Finds the cost of one of each item, made the cheapest way with the recipes and raw material costs.
+    ///
+    /// Each item is optimized on its own, as the only output, so a raw material costs the cheaper of its own cost and
+    /// crafting it. Everything that is about the plan as a whole rather than one item is ignored: the targets, ratio
+    /// groups, rules, raw material availability, production and surplus limits, locked uses, maximum number of
+    /// different recipes and cost per different recipe.
+    pub fn unit_costs(&self, weights: &Costs) -> BTreeMap<Item, Result<Costs, SolveError>> {
+        let mut model = self.clone();
+        model.goal = Goal::MinimizeCost;
+        model.ratio_groups.clear();
+        model.rules.clear();
+        model.max_production.clear();
+        model.max_surplus.clear();
+        model.max_recipes = None;
+        model.recipe_penalty = None;
+        for recipe in model.recipes.iter_mut() {
+            recipe.min_uses = 0.;
+            recipe.max_uses = None;
+        }
+        for role in model.items.values_mut() {
+            match role {
+                ItemRole::Target { .. } => *role = ItemRole::Intermediate,
+                ItemRole::Raw { available, .. } => *available = None,
+                ItemRole::Intermediate => (),
+            }
+        }
+
+        // The item is asked for as a ratio group of its own, which keeps its role, so a raw material can still be
+        // taken.
+        self.items
+            .keys()
+            .map(|item| {
+                model.ratio_groups = vec![RatioGroup {
+                    members: vec![(item.clone(), 1.)],
+                    amount: 1.,
+                    priority: 0,
+                }];
+                (item.clone(), model.solve(weights).map(|plan| plan.costs))
+            })
+            .collect()
+    }
+
+    /// Computes Pareto-optimal plans between two cost dimensions (`None` is the main one), with `steps + 1` optimizations.
+    ///
+    /// The other dimensions are ignored. The plans are sorted by increasing cost in `x`, and the duplicates are removed.
+    /// Only meaningful when minimizing the cost.
+    pub fn pareto(
+        &self,
+        x: Option<usize>,
+        y: Option<usize>,
+        method: ParetoMethod,
+        steps: usize,
+    ) -> Result<Vec<Plan>, SolveError> {
+        let steps = steps.max(1);
+        let weights = |wx: f64, wy: f64| {
+            let mut weights = Costs::default();
+            weights.set(x, wx);
+            weights.set(y, wy);
+            weights
+        };
+
+        let mut plans = match method {
+            ParetoMethod::WeightSweep => (0..=steps)
+                .map(|k| {
+                    let lambda = k as f64 / steps as f64;
+                    self.solve(&weights(
+                        lambda.max(TIE_BREAK),
+                        (1. - lambda).max(TIE_BREAK),
+                    ))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            ParetoMethod::Epsilon => {
+                let y_max = self.solve(&weights(1., TIE_BREAK))?.costs.get(y);
+                let y_min = self.solve(&weights(TIE_BREAK, 1.))?.costs.get(y);
+                (0..=steps)
+                    .map(|k| {
+                        let epsilon = y_max - (y_max - y_min) * k as f64 / steps as f64;
+                        let tolerance = 1e-9 * (1. + epsilon.abs());
+                        self.solve_bounded(&weights(1., TIE_BREAK), &[(y, epsilon + tolerance)])
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+        };
+
+        let close = |a: f64, b: f64| (a - b).abs() <= 1e-6 * (1. + a.abs().max(b.abs()));
+        plans.sort_by(|a, b| a.costs.get(x).total_cmp(&b.costs.get(x)));
+        plans.dedup_by(|a, b| {
+            close(a.costs.get(x), b.costs.get(x)) && close(a.costs.get(y), b.costs.get(y))
+        });
+        Ok(plans)
+    }
+
+    /// Finds the recipes and items that can help to meet the targets. The others are left out of the linear program.
+    ///
+    /// The search goes backwards from the targets, the ratio groups, the rules and the recipes with a minimum number
+    /// of uses: from an item to the recipes that make it and their ingredients, from a tag to its members, and to the
+    /// fuels once a recipe with a fuel slot is kept. The products of the kept recipes are kept too, and so are the
+    /// recipes that use a product with a maximum surplus, as they may be needed to keep under it.
+    fn relevant(&self) -> (Vec<bool>, HashSet<&Item>) {
+        let mut kept = vec![false; self.recipes.len()];
+        let mut items = HashSet::new();
+        let mut needed = HashSet::new();
+        let mut limited = HashSet::new(); // Products with a maximum surplus
+        let mut consumers = Vec::new(); // Same, whose uses are still to be kept
+        let mut fuels = false;
+
+        let mut queue: Vec<&Item> = self
+            .items
+            .iter()
+            .filter(|(_, role)| matches!(role, ItemRole::Target { .. }))
+            .map(|(item, _)| item)
+            .chain(
+                self.ratio_groups
+                    .iter()
+                    .flat_map(|group| group.members.iter().map(|(item, _)| item)),
+            )
+            .collect();
+        let mut recipes: Vec<usize> = (0..self.recipes.len())
+            .filter(|index| self.recipes[*index].min_uses > 0.)
+            .collect();
+        for rule in self.rules.iter() {
+            for (_, reference) in rule.get_terms() {
+                match reference {
+                    Reference::Uses(label) => {
+                        recipes.extend((0..self.recipes.len()).filter(|index| {
+                            self.recipes[*index].recipe.get_labels().contains(label)
+                        }))
+                    }
+                    Reference::Produced(item) | Reference::Used(item) => queue.push(item),
+                }
+            }
+        }
+
+        loop {
+            for index in recipes.drain(..) {
+                if kept[index] {
+                    continue;
+                }
+                kept[index] = true;
+                let recipe = &self.recipes[index].recipe;
+                queue.extend(recipe.get_ingredients().iter().map(|(item, _)| item));
+                for (product, _, _) in recipe.get_products() {
+                    items.insert(product);
+                    if self.max_surplus.contains_key(product) && limited.insert(product) {
+                        consumers.push(product);
+                    }
+                }
+                if recipe.get_fuel().is_some() && !fuels {
+                    fuels = true;
+                    queue.extend(self.fuels.keys());
+                }
+            }
+
+            for item in consumers.drain(..) {
+                recipes.extend((0..self.recipes.len()).filter(|index| {
+                    !kept[*index]
+                        && self.recipes[*index]
+                            .recipe
+                            .get_ingredients()
+                            .iter()
+                            .any(|(ingredient, _)| ingredient == item)
+                }));
+            }
+
+            let Some(item) = queue.pop() else {
+                if recipes.is_empty() {
+                    break;
+                }
+                continue;
+            };
+            items.insert(item);
+            if !needed.insert(item) {
+                continue;
+            }
+            if let Some(members) = self.tags.get(item) {
+                queue.extend(members.iter());
+            }
+            recipes.extend((0..self.recipes.len()).filter(|index| {
+                !kept[*index]
+                    && self.recipes[*index]
+                        .recipe
+                        .get_products()
+                        .iter()
+                        .any(|(product, _, _)| product == item)
+            }));
+        }
+
+        (kept, items)
+    }
+
+    fn program(&self) -> Result<Program, SolveError> {
+        let (kept, relevant) = self.relevant();
+
+        // One variable per kept recipe, between the bounds of its uses.
+        let mut problem = Variables::default();
+        let recipes: Vec<usize> = (0..self.recipes.len())
+            .filter(|index| kept[*index])
+            .collect();
+        let variables: Vec<Variable> = recipes
+            .iter()
+            .map(|index| {
+                let ModelRecipe {
+                    recipe,
+                    min_uses,
+                    max_uses,
+                    ..
+                } = &self.recipes[*index];
+                let names = |items: Vec<&Item>| {
+                    items
+                        .iter()
+                        .map(|item| item.get_name().as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                let name = format!(
+                    "recipe_{index} {} from {}",
+                    names(
+                        recipe
+                            .get_products()
+                            .iter()
+                            .map(|(item, _, _)| item)
+                            .collect()
+                    ),
+                    names(
+                        recipe
+                            .get_ingredients()
+                            .iter()
+                            .map(|(item, _)| item)
+                            .collect()
+                    )
+                );
+                problem.add(name, *min_uses, *max_uses)
+            })
+            .collect();
+
+        // Item expressions. Hash map with (expr_prod expr_uses)
+        let mut item_expressions: HashMap<Item, (Expression, Expression)> = self
+            .items
+            .keys()
+            .filter(|item| relevant.contains(item))
+            .map(|item| (item.clone(), Default::default()))
+            .collect();
+        let pruned_items: Vec<Item> = self
+            .items
+            .keys()
+            .filter(|item| !relevant.contains(item))
+            .cloned()
+            .collect();
+
+        // Cost of each dimension, main one first.
+        let mut costs: Vec<(Option<usize>, Expression)> = Some(None)
+            .into_iter()
+            .chain(self.dimensions.iter().map(|id| Some(*id)))
+            .map(|dimension| (dimension, Expression::default()))
+            .collect();
+
+        // Smelting operations required by the fuel slots.
+        let mut smelts_needed = Expression::default();
+
+        // For each recipe, edit the expressions of the items and the process costs.
+        for (
+            index,
+            ModelRecipe {
+                recipe,
+                products_mult,
+                ingredients_mult,
+                ..
+            },
+        ) in recipes
+            .iter()
+            .map(|index| &self.recipes[*index])
+            .enumerate()
+        {
+            if let Some(fuel) = recipe.get_fuel() {
+                smelts_needed.add_mul(fuel, variables[index]);
+            }
+            for (dimension, expr) in costs.iter_mut() {
+                let cost = match dimension {
+                    None => recipe.get_cost(),
+                    Some(id) => recipe.get_dimension_costs().get(id).copied(),
+                };
+                if let Some(cost) = cost.filter(|cost| *cost != 0.) {
+                    expr.add_mul(cost, variables[index]);
+                }
+            }
+
+            for (item, qty, product) in recipe
+                .get_ingredients()
+                .iter()
+                .map(|(item, qty)| (item, qty * ingredients_mult, false))
+                .chain(recipe.get_products().iter().map(|(item, qty, prob)| {
+                    (item, qty.expected() * prob.get() * products_mult, true)
+                }))
+            {
+                let Some((prod_expr, uses_expr)) = item_expressions.get_mut(item) else {
+                    return Err(SolveError::MissingItem(item.clone()));
+                };
+                if product {
+                    prod_expr.add_mul(qty, variables[index])
+                } else {
+                    uses_expr.add_mul(qty, variables[index])
+                }
+            }
+        }
+
+        // Fuel: one variable per fuel item, the amount burnt. The optimizer picks the cheapest.
+        let mut smelts_provided = Expression::default();
+        let mut burnt_vars = Vec::new();
+        for (item, smelts) in self
+            .fuels
+            .iter()
+            .filter(|(item, _)| relevant.contains(item))
+        {
+            let burnt = problem.add(format!("{} burnt", item.get_name()), 0., None);
+            burnt_vars.push((item.clone(), burnt));
+            smelts_provided.add_mul(*smelts, burnt);
+            let Some((_, uses_expr)) = item_expressions.get_mut(item) else {
+                return Err(SolveError::MissingItem(item.clone()));
+            };
+            uses_expr.add_mul(1, burnt);
+        }
+
+        // Tags: one variable per member, the amount used as the tag. The optimizer picks the cheapest mix.
+        let mut tag_uses = Vec::new();
+        for (tag, members) in self.tags.iter().filter(|(tag, _)| relevant.contains(tag)) {
+            for member in members {
+                let used = problem.add(
+                    format!("{} as {}", member.get_name(), tag.get_name()),
+                    0.,
+                    None,
+                );
+                tag_uses.push((tag.clone(), member.clone(), used));
+                let Some((prod_expr, _)) = item_expressions.get_mut(tag) else {
+                    return Err(SolveError::MissingItem(tag.clone()));
+                };
+                prod_expr.add_mul(1, used);
+                let Some((_, uses_expr)) = item_expressions.get_mut(member) else {
+                    return Err(SolveError::MissingItem(member.clone()));
+                };
+                uses_expr.add_mul(1, used);
+            }
+        }
+
+        // Purchases: one variable per item that can be bought, the amount bought.
+        let mut bought = Vec::new();
+        if self.goal == Goal::MinimizeCost {
+            for (item, price) in self
+                .prices
+                .iter()
+                .filter(|(item, _)| relevant.contains(item))
+            {
+                let var = problem.add(format!("{} bought", item.get_name()), 0., None);
+                let Some((prod_expr, _)) = item_expressions.get_mut(item) else {
+                    return Err(SolveError::MissingItem(item.clone()));
+                };
+                prod_expr.add_mul(1, var);
+                for (dimension, expr) in costs.iter_mut() {
+                    let cost = price.get(*dimension);
+                    if cost != 0. {
+                        expr.add_mul(cost, var);
+                    }
+                }
+                bought.push((item.clone(), var));
+            }
+        }
+
+        // Go through the item list and build the constraints / targets / costs
+        let mut outputs: BTreeMap<u32, Expression> = BTreeMap::new();
+        let mut constraints = Vec::new();
+
+        // Recipes used: one binary variable per recipe, that has to be 1 for the recipe to be crafted.
+        // The uses of a recipe are then bounded: by its maximum number of uses if any, otherwise by a limit that
+        // grows with what the model asks for and shrinks with the yield of the recipe. A plan that reaches that limit
+        // is rejected.
+        let mut active = Vec::new();
+        let mut use_limits = Vec::new();
+        if self.max_recipes.is_some() || self.recipe_penalty.is_some() {
+            let demand: f64 = self
+                .items
+                .values()
+                .map(|role| match (role, self.goal) {
+                    (ItemRole::Target { amount, .. }, Goal::MinimizeCost) => *amount,
+                    (
+                        ItemRole::Raw {
+                            available: Some(available),
+                            ..
+                        },
+                        Goal::MaximizeOutput,
+                    ) => *available,
+                    _ => 0.,
+                })
+                .chain(self.ratio_groups.iter().map(|group| match self.goal {
+                    Goal::MinimizeCost => {
+                        group.amount * group.members.iter().map(|(_, ratio)| ratio).sum::<f64>()
+                    }
+                    Goal::MaximizeOutput => 0.,
+                }))
+                .chain(self.recipes.iter().map(|recipe| recipe.min_uses))
+                .sum();
+            let mut count = Expression::default();
+            for (index, var) in recipes.iter().zip(variables.iter()) {
+                let used = problem.add_binary(format!("recipe_{index}_active"));
+                let ModelRecipe {
+                    recipe,
+                    products_mult,
+                    max_uses,
+                    ..
+                } = &self.recipes[*index];
+                let limit = match max_uses {
+                    Some(max_uses) => *max_uses,
+                    None => {
+                        // Smallest amount of a product made per craft.
+                        let recipe_yield = recipe
+                            .get_products()
+                            .iter()
+                            .map(|(_, qty, prob)| qty.expected() * prob.get() * products_mult)
+                            .filter(|amount| *amount > 0.)
+                            .fold(1., f64::min);
+                        let limit = USES_PER_DEMAND * demand.max(1.) / recipe_yield;
+                        use_limits.push((*index, *var, limit));
+                        limit
+                    }
+                };
+                let mut bound = Expression::default();
+                bound.add_mul(limit, used);
+                constraints.push(Row::new(
+                    format!("recipe_{index}_used"),
+                    *var,
+                    Relation::LessOrEqual,
+                    bound,
+                ));
+                count.add_mul(1, used);
+                active.push(used);
+            }
+            if let Some(max) = self.max_recipes {
+                constraints.push(Row::new(
+                    "max_recipes",
+                    count,
+                    Relation::LessOrEqual,
+                    max as f64,
+                ));
+            }
+        }
+
+        // Ratio groups: one variable per group, the number of complete sets. Each set claims its members, on top of
+        // their other demands.
+        let mut claimed: HashMap<&Item, Expression> = HashMap::new();
+        for (index, group) in self.ratio_groups.iter().enumerate() {
+            if !group.members.iter().any(|(_, ratio)| *ratio > 0.) {
+                return Err(SolveError::EmptyRatioGroup(index));
+            }
+            let sets = match self.goal {
+                Goal::MinimizeCost => {
+                    problem.add(format!("ratio_group_{index}_sets"), group.amount, None)
+                }
+                Goal::MaximizeOutput => {
+                    let sets = problem.add(format!("ratio_group_{index}_sets"), 0., None);
+                    outputs
+                        .entry(group.priority)
+                        .or_default()
+                        .add_mul(group.amount, sets);
+                    sets
+                }
+            };
+            for (member, ratio) in group.members.iter() {
+                if !item_expressions.contains_key(member) {
+                    return Err(SolveError::MissingItem(member.clone()));
+                }
+                claimed.entry(member).or_default().add_mul(*ratio, sets);
+            }
+        }
+        let mut fuel = None;
+        if recipes
+            .iter()
+            .any(|index| self.recipes[*index].recipe.get_fuel().is_some())
+        {
+            if self.fuels.is_empty() {
+                return Err(SolveError::NoFuel);
+            }
+            fuel = Some(constraints.len());
+            constraints.push(Row::new(
+                "fuel",
+                smelts_provided,
+                Relation::GreaterOrEqual,
+                smelts_needed,
+            ));
+        }
+        let mut balances = HashMap::new();
+        for (item, role) in self
+            .items
+            .iter()
+            .filter(|(item, _)| relevant.contains(item))
+        {
+            let Some((prod_expr, uses_expr)) = item_expressions.get(item) else {
+                return Err(SolveError::MissingItem(item.clone()));
+            };
+
+            let mut expression = prod_expr.clone() - uses_expr.clone();
+            if let Some(sets) = claimed.remove(item) {
+                expression -= sets;
+            }
+
+            let index = constraints.len();
+            let name = item.get_name().clone();
+            match role {
+                ItemRole::Target { amount, priority } => match self.goal {
+                    Goal::MinimizeCost => constraints.push(Row::new(
+                        name,
+                        expression,
+                        Relation::GreaterOrEqual,
+                        *amount,
+                    )),
+                    Goal::MaximizeOutput => {
+                        outputs
+                            .entry(*priority)
+                            .or_default()
+                            .add_mul(*amount, expression.clone());
+                        constraints.push(Row::new(name, expression, Relation::GreaterOrEqual, 0.))
+                    }
+                },
+                ItemRole::Raw {
+                    costs: unit_costs,
+                    available,
+                } => {
+                    if available.is_none() && self.goal == Goal::MaximizeOutput {
+                        return Err(SolveError::Unlimited(item.clone()));
+                    }
+                    // Only the amount taken is paid for, so a surplus earns nothing.
+                    let taken = problem.add(
+                        format!("{} taken from raw", item.get_name()),
+                        0.,
+                        *available,
+                    );
+                    for (dimension, expr) in costs.iter_mut() {
+                        let cost = unit_costs.get(*dimension);
+                        if cost != 0. {
+                            expr.add_mul(cost, taken);
+                        }
+                    }
+                    constraints.push(Row::new(
+                        name,
+                        expression + taken,
+                        Relation::GreaterOrEqual,
+                        0.,
+                    ));
+                }
+                ItemRole::Intermediate => {
+                    constraints.push(Row::new(name, expression, Relation::GreaterOrEqual, 0.))
+                }
+            }
+            if constraints.len() > index {
+                balances.insert(item.clone(), index);
+            }
+        }
+
+        // Production and storage limits.
+        for (item, max) in self
+            .max_production
+            .iter()
+            .filter(|(item, _)| relevant.contains(item))
+        {
+            let Some((prod_expr, _)) = item_expressions.get(item) else {
+                return Err(SolveError::MissingItem(item.clone()));
+            };
+            constraints.push(Row::new(
+                format!("{} production", item.get_name()),
+                prod_expr.clone(),
+                Relation::LessOrEqual,
+                *max,
+            ));
+        }
+        for (item, max) in self
+            .max_surplus
+            .iter()
+            .filter(|(item, _)| relevant.contains(item))
+        {
+            let Some((prod_expr, uses_expr)) = item_expressions.get(item) else {
+                return Err(SolveError::MissingItem(item.clone()));
+            };
+            constraints.push(Row::new(
+                format!("{} surplus", item.get_name()),
+                prod_expr.clone() - uses_expr.clone(),
+                Relation::LessOrEqual,
+                *max,
+            ));
+        }
+
+        // Rules written by the user.
+        for (index, rule) in self.rules.iter().enumerate() {
+            let mut expr = Expression::default();
+            for (coef, reference) in rule.get_terms() {
+                match reference {
+                    Reference::Uses(label) => {
+                        let mut found = false;
+                        for (index, var) in recipes.iter().zip(variables.iter()) {
+                            if self.recipes[*index].recipe.get_labels().contains(label) {
+                                expr.add_mul(*coef, *var);
+                                found = true;
+                            }
+                        }
+                        if !found {
+                            return Err(SolveError::UnknownReference(reference.clone()));
+                        }
+                    }
+                    Reference::Produced(item) | Reference::Used(item) => {
+                        let Some((prod_expr, uses_expr)) = item_expressions.get(item) else {
+                            return Err(SolveError::UnknownReference(reference.clone()));
+                        };
+                        let item_expr = match reference {
+                            Reference::Produced(_) => prod_expr,
+                            _ => uses_expr,
+                        };
+                        expr.add_mul(*coef, item_expr.clone());
+                    }
+                }
+            }
+            constraints.push(Row::new(
+                format!("rule_{index}"),
+                expr,
+                rule.get_relation(),
+                rule.get_constant(),
+            ));
+        }
+
+        Ok(Program {
+            variables: problem,
+            active,
+            constraints,
+            balances,
+            fuel,
+            expressions: Expressions {
+                variables,
+                recipe_count: self.recipes.len(),
+                recipes,
+                pruned_items,
+                item_expressions,
+                costs,
+                outputs,
+                bought,
+                burnt: burnt_vars,
+                tag_uses,
+                use_limits,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recipes::{Count, Probability};
+
+    fn item(name: &str) -> Item {
+        Item::new(name)
+    }
+
+    /// A recipe that makes one of each product from one of each ingredient.
+    fn recipe(ingredients: &[&str], products: &[&str]) -> ModelRecipe {
+        ModelRecipe {
+            recipe: Recipe::new(
+                ingredients.iter().map(|name| (item(name), 1.)).collect(),
+                products
+                    .iter()
+                    .map(|name| (item(name), Count::Fixed(1.), Default::default()))
+                    .collect(),
+            ),
+            products_mult: 1.,
+            ingredients_mult: 1.,
+            min_uses: 0.,
+            max_uses: None,
+        }
+    }
+
+    fn raw(cost: f64) -> ItemRole {
+        ItemRole::Raw {
+            costs: Costs {
+                main: cost,
+                ..Default::default()
+            },
+            available: None,
+        }
+    }
+
+    fn target(amount: f64) -> ItemRole {
+        ItemRole::Target {
+            amount,
+            priority: 0,
+        }
+    }
+
+    fn weights() -> Costs {
+        Costs {
+            main: 1.,
+            ..Default::default()
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{actual} is not {expected}"
+        );
+    }
+
+    #[test]
+    fn cheapest_fuel() {
+        let mut smelting = recipe(&["ore"], &["ingot"]);
+        smelting.recipe = smelting.recipe.with_fuel(Some(1.));
+        let model = Model {
+            recipes: vec![smelting],
+            items: BTreeMap::from([
+                (item("ore"), raw(1.)),
+                (item("ingot"), target(8.)),
+                (item("coal"), raw(4.)),
+                (item("wood"), raw(1.)),
+            ]),
+            // Coal costs 0.5 per smelt, wood 2/3.
+            fuels: BTreeMap::from([(item("coal"), 8.), (item("wood"), 1.5)]),
+            ..Default::default()
+        };
+        let plan = model.solve(&weights()).unwrap();
+        assert_close(plan.burnt[&item("coal")], 1.);
+        assert_close(plan.burnt[&item("wood")], 0.);
+        assert_close(plan.costs.main, 12.);
+    }
+
+    #[test]
+    fn tag_members() {
+        let mut model = Model {
+            recipes: vec![recipe(&["planks"], &["stick"])],
+            items: BTreeMap::from([
+                (item("planks"), ItemRole::Intermediate),
+                (item("oak planks"), raw(3.)),
+                (item("birch planks"), raw(1.)),
+                (item("stick"), target(2.)),
+            ]),
+            tags: BTreeMap::from([(
+                item("planks"),
+                vec![item("oak planks"), item("birch planks")],
+            )]),
+            ..Default::default()
+        };
+        let used = |plan: &Plan, member: &str| {
+            plan.tag_uses[&item("planks")]
+                .iter()
+                .find(|(other, _)| *other == item(member))
+                .map(|(_, amount)| *amount)
+                .unwrap()
+        };
+        let plan = model.solve(&weights()).unwrap();
+        assert_close(used(&plan, "birch planks"), 2.);
+        assert_close(used(&plan, "oak planks"), 0.);
+
+        model.items.insert(item("birch planks"), raw(5.));
+        let plan = model.solve(&weights()).unwrap();
+        assert_close(used(&plan, "oak planks"), 2.);
+        assert_close(plan.costs.main, 6.);
+    }
+
+    #[test]
+    fn maximize_within_availability() {
+        let mut smelting = recipe(&["ore"], &["ingot"]);
+        smelting.recipe.get_mut_ingredients()[0].1 = 2.;
+        let mut model = Model {
+            recipes: vec![smelting],
+            items: BTreeMap::from([
+                (
+                    item("ore"),
+                    ItemRole::Raw {
+                        costs: Costs::default(),
+                        available: Some(10.),
+                    },
+                ),
+                (item("ingot"), target(1.)),
+            ]),
+            goal: Goal::MaximizeOutput,
+            ..Default::default()
+        };
+        let plan = model.solve(&weights()).unwrap();
+        assert_close(plan.item_stats[&item("ingot")].0, 5.);
+        assert_close(plan.item_stats[&item("ore")].1, 10.);
+        assert_close(plan.outputs[&0], 5.);
+
+        model.items.insert(item("ore"), raw(1.));
+        assert!(matches!(
+            model.solve(&weights()),
+            Err(SolveError::Unlimited(_))
+        ));
+    }
+
+    #[test]
+    fn ratio_group_sets() {
+        let model = Model {
+            recipes: vec![recipe(&["ore"], &["gear"]), recipe(&["ore"], &["axle"])],
+            items: BTreeMap::from([
+                (item("ore"), raw(1.)),
+                (item("gear"), target(4.)),
+                (item("axle"), ItemRole::Intermediate),
+            ]),
+            ratio_groups: vec![RatioGroup {
+                members: vec![(item("gear"), 2.), (item("axle"), 1.)],
+                amount: 3.,
+                priority: 0,
+            }],
+            ..Default::default()
+        };
+        let plan = model.solve(&weights()).unwrap();
+        // Three sets of two gears and an axle, and the four gears of the target on top.
+        assert_close(plan.item_stats[&item("gear")].0, 10.);
+        assert_close(plan.item_stats[&item("axle")].0, 3.);
+        assert_close(plan.costs.main, 13.);
+    }
+
+    #[test]
+    fn priorities_come_first() {
+        let available = |amount| ItemRole::Raw {
+            costs: Costs::default(),
+            available: Some(amount),
+        };
+        let model = Model {
+            recipes: vec![
+                recipe(&["ore"], &["rail"]),
+                recipe(&["ore"], &["hopper"]),
+                recipe(&["scrap"], &["hopper"]),
+            ],
+            items: BTreeMap::from([
+                (item("ore"), available(10.)),
+                (item("scrap"), available(5.)),
+                (
+                    item("rail"),
+                    ItemRole::Target {
+                        amount: 1.,
+                        priority: 1,
+                    },
+                ),
+                // Worth much more, but only once the rails are maximized.
+                (
+                    item("hopper"),
+                    ItemRole::Target {
+                        amount: 100.,
+                        priority: 0,
+                    },
+                ),
+            ]),
+            goal: Goal::MaximizeOutput,
+            ..Default::default()
+        };
+        let plan = model.solve(&weights()).unwrap();
+        // A level keeps its output up to a tolerance of 1e-6 of it, for the solver.
+        let rails = plan.item_stats[&item("rail")].0;
+        assert!((10. - rails).abs() < 1e-4, "{rails} is not 10");
+        assert!((plan.item_stats[&item("hopper")].0 - 5.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn max_recipes() {
+        let mut cheap = recipe(&["sand"], &["plate"]);
+        cheap.max_uses = Some(1.);
+        let mut model = Model {
+            recipes: vec![cheap, recipe(&["ore"], &["plate"])],
+            items: BTreeMap::from([
+                (item("sand"), raw(1.)),
+                (item("ore"), raw(5.)),
+                (item("plate"), target(3.)),
+            ]),
+            ..Default::default()
+        };
+        let used = |plan: &Plan| plan.recipe_uses.iter().filter(|uses| **uses > 1e-6).count();
+        let plan = model.solve(&weights()).unwrap();
+        assert_eq!(used(&plan), 2);
+        assert_close(plan.costs.main, 11.);
+
+        model.max_recipes = Some(1);
+        let plan = model.solve(&weights()).unwrap();
+        assert_eq!(used(&plan), 1);
+        assert_close(plan.recipe_uses[0], 0.);
+        assert!(plan.item_stats[&item("plate")].0 >= 3. - 1e-6);
+    }
+
+    #[test]
+    fn rare_drop_with_max_recipes() {
+        let mut sifting = recipe(&["gravel"], &["diamond"]);
+        sifting.recipe.get_mut_products()[0].2 = Probability::new(1, 10_000).unwrap();
+        let model = Model {
+            recipes: vec![sifting],
+            items: BTreeMap::from([(item("gravel"), raw(1.)), (item("diamond"), target(2.))]),
+            max_recipes: Some(1),
+            ..Default::default()
+        };
+        let plan = model.solve(&weights()).unwrap();
+        assert_close(plan.recipe_uses[0], 20_000.);
+    }
+
+    #[test]
+    fn locked_uses() {
+        let mut model = Model {
+            recipes: vec![recipe(&["sand"], &["plate"]), recipe(&["ore"], &["plate"])],
+            items: BTreeMap::from([
+                (item("sand"), raw(1.)),
+                (item("ore"), raw(5.)),
+                (item("plate"), target(10.)),
+            ]),
+            ..Default::default()
+        };
+        model.recipes[1].min_uses = 3.;
+        model.recipes[1].max_uses = Some(3.);
+        let plan = model.solve(&weights()).unwrap();
+        assert_close(plan.recipe_uses[0], 7.);
+        assert_close(plan.recipe_uses[1], 3.);
+
+        model.recipes[1].min_uses = 0.;
+        model.recipes[1].max_uses = None;
+        model.recipes[0].max_uses = Some(4.);
+        let plan = model.solve(&weights()).unwrap();
+        assert_close(plan.recipe_uses[0], 4.);
+        assert_close(plan.recipe_uses[1], 6.);
+    }
+
+    #[test]
+    fn pruning() {
+        let mut model = Model {
+            recipes: vec![
+                recipe(&["ore"], &["ingot"]),
+                recipe(&["ingot"], &["gear"]),
+                recipe(&["sand"], &["glass"]),
+                recipe(&["scrap"], &["ore"]),
+            ],
+            items: BTreeMap::from([
+                (item("ore"), raw(3.)),
+                (item("scrap"), raw(1.)),
+                (item("ingot"), target(2.)),
+                (item("gear"), ItemRole::Intermediate),
+                (item("sand"), raw(1.)),
+                (item("glass"), ItemRole::Intermediate),
+            ]),
+            ..Default::default()
+        };
+        // Only the recipes that can help to make ingots are kept, however far back.
+        let plan = model.solve(&weights()).unwrap();
+        assert_eq!(plan.pruned_recipes, 2);
+        assert_eq!(plan.pruned_items, 3);
+        assert_close(plan.recipe_uses[3], 2.);
+        assert_close(plan.recipe_uses[1], 0.);
+        assert_close(plan.costs.main, 2.);
+
+        // A locked recipe is kept, with what it uses.
+        model.recipes[2].min_uses = 1.;
+        let plan = model.solve(&weights()).unwrap();
+        assert_eq!(plan.pruned_recipes, 1);
+        assert_eq!(plan.pruned_items, 1);
+        assert_close(plan.recipe_uses[2], 1.);
+        assert_close(plan.costs.main, 3.);
+    }
+
+    #[test]
+    fn unit_costs() {
+        let mut model = Model {
+            recipes: vec![recipe(&["ore"], &["ingot"]), recipe(&["ingot"], &["gear"])],
+            items: BTreeMap::from([
+                (
+                    item("ore"),
+                    ItemRole::Raw {
+                        costs: weights(),
+                        available: Some(0.),
+                    },
+                ),
+                (item("ingot"), raw(5.)),
+                (item("gear"), target(10.)),
+            ]),
+            ..Default::default()
+        };
+        model.recipes[1].recipe.get_mut_ingredients()[0].1 = 2.;
+        let costs = model.unit_costs(&weights());
+        // Crafting an ingot is cheaper than taking one, and the availability of the ore is ignored.
+        assert_close(costs[&item("ore")].as_ref().unwrap().main, 1.);
+        assert_close(costs[&item("ingot")].as_ref().unwrap().main, 1.);
+        assert_close(costs[&item("gear")].as_ref().unwrap().main, 2.);
+
+        model.items.insert(item("ingot"), raw(0.5));
+        let costs = model.unit_costs(&weights());
+        assert_close(costs[&item("ingot")].as_ref().unwrap().main, 0.5);
+        assert_close(costs[&item("gear")].as_ref().unwrap().main, 1.);
+    }
+
+    #[test]
+    fn max_surplus() {
+        let mut dumping = recipe(&["slag"], &["gravel"]);
+        dumping.recipe = dumping.recipe.with_cost(Some(1.));
+        let mut model = Model {
+            recipes: vec![recipe(&["ore"], &["ingot", "slag"]), dumping],
+            items: BTreeMap::from([
+                (item("ore"), raw(1.)),
+                (item("ingot"), target(5.)),
+                (item("slag"), ItemRole::Intermediate),
+                (item("gravel"), ItemRole::Intermediate),
+            ]),
+            ..Default::default()
+        };
+        let plan = model.solve(&weights()).unwrap();
+        assert_close(plan.recipe_uses[1], 0.);
+
+        model.max_surplus.insert(item("slag"), 2.);
+        let plan = model.solve(&weights()).unwrap();
+        let (prod, uses) = plan.item_stats[&item("slag")];
+        assert_close(prod - uses, 2.);
+        assert_close(plan.recipe_uses[1], 3.);
+    }
+}
@@ -0,0 +1,471 @@
+//! Everything to do with the [`Share`] of each target in the raw materials and costs of a [`Plan`].
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::ui::Item;
+
+use super::{Costs, ItemRole, Model, Plan};
+
+/// Most items processed when following the flows of one target, in case a loop of recipes does not fade out.
+const MAX_STEPS: usize = 100_000;
+
+/// Part of the raw materials, purchases and costs of a [`Plan`] that goes into one target.
+#[derive(Debug, Clone, Default)]
+pub struct Share {
+    /// Amount of each raw material.
+    pub raws: BTreeMap<Item, f64>,
+    /// Amount bought of each item.
+    pub bought: BTreeMap<Item, f64>,
+    /// Costs, raw materials, purchases and process costs included.
+    pub costs: Costs,
+    /// The flows were still going when the steps ran out, so part of the share is missing.
+    pub incomplete: bool,
+}
+
+impl Share {
+    fn add_costs(&mut self, costs: impl IntoIterator<Item = (Option<usize>, f64)>, amount: f64) {
+        for (dimension, cost) in costs {
+            self.costs
+                .set(dimension, self.costs.get(dimension) + cost * amount);
+        }
+    }
+}
+
+/// Where the supply of an item comes from, in a solved plan.
+#[derive(Default)]
+struct Supply {
+    total: f64,
+    recipes: Vec<(usize, f64)>, // Recipe index, amount produced
+    members: Vec<(Item, f64)>,  // Tag member, amount used as the tag
+    bought: f64,
+    raw: f64, // Amount net consumed of a raw material
+}
+
+impl Model {
+    /// Splits the raw materials, purchases and costs of a plan between its targets, ratio group members included.
+    ///
+    /// The plan is followed backwards from each target. An item used somewhere comes from each of its sources in
+    /// proportion to what they supply. The ingredients, fuel and process cost of a recipe go to its products in
+    /// proportion to the amount of them that is used, so the surplus of a byproduct is not charged to anything.
+    ///
+    /// The items are followed in an order where each one comes after every item that draws on it, so it is only
+    /// followed once, with its whole demand. Only a loop of recipes sends demand back to an item already followed, and
+    /// that demand shrinks with each turn, by the part of the supply of the loop that comes from outside of it. A loop
+    /// that does not fade out is only followed for a bounded number of steps. The share is then marked incomplete.
+    pub fn shares(&self, plan: &Plan) -> BTreeMap<Item, Share> {
+        let dimensions: Vec<Option<usize>> = Some(None)
+            .into_iter()
+            .chain(self.dimensions.iter().map(|id| Some(*id)))
+            .collect();
+
+        let outputs: Vec<&Item> = self
+            .items
+            .iter()
+            .filter(|(_, role)| matches!(role, ItemRole::Target { .. }))
+            .map(|(item, _)| item)
+            .chain(
+                self.ratio_groups
+                    .iter()
+                    .flat_map(|group| group.members.iter().map(|(item, _)| item)),
+            )
+            .collect();
+
+        let stats = |item: &Item| plan.item_stats.get(item).copied().unwrap_or_default();
+
+        // Sources of each item.
+        let mut supplies: HashMap<&Item, Supply> = HashMap::new();
+        for (index, (recipe, uses)) in self.recipes.iter().zip(plan.recipe_uses.iter()).enumerate()
+        {
+            for (item, qty, prob) in recipe.recipe.get_products() {
+                let amount = qty.expected() * prob.get() * recipe.products_mult * uses;
+                if amount > 0. {
+                    supplies
+                        .entry(item)
+                        .or_default()
+                        .recipes
+                        .push((index, amount));
+                }
+            }
+        }
+        for (tag, members) in plan.tag_uses.iter() {
+            for (member, amount) in members {
+                if *amount > 0. {
+                    supplies
+                        .entry(tag)
+                        .or_default()
+                        .members
+                        .push((member.clone(), *amount));
+                }
+            }
+        }
+        for (item, amount) in plan.bought.iter() {
+            supplies.entry(item).or_default().bought = *amount;
+        }
+        for (item, role) in self.items.iter() {
+            let (prod, uses) = stats(item);
+            let supply = supplies.entry(item).or_default();
+            if let ItemRole::Raw { .. } = role {
+                supply.raw = (uses - prod).max(0.);
+            }
+            supply.total = prod + supply.raw;
+        }
+
+        // Part of the supply of each item that is used, or is an output.
+        let used = |item: &Item| -> f64 {
+            if outputs.contains(&item) {
+                return 1.;
+            }
+            let (prod, uses) = stats(item);
+            let total = supplies
+                .get(item)
+                .map(|supply| supply.total)
+                .unwrap_or_default();
+            if total > 0. {
+                (1. - (prod - uses).max(0.) / total).clamp(0., 1.)
+            } else {
+                0.
+            }
+        };
+
+        // Amount of the products of each recipe that is used, per craft.
+        let useful: Vec<f64> = self
+            .recipes
+            .iter()
+            .map(|recipe| {
+                recipe
+                    .recipe
+                    .get_products()
+                    .iter()
+                    .map(|(item, qty, prob)| {
+                        qty.expected() * prob.get() * recipe.products_mult * used(item)
+                    })
+                    .sum()
+            })
+            .collect();
+
+        // Items the supply of an item draws on.
+        let draws_on = |item: &Item| -> Vec<&Item> {
+            let Some(supply) = supplies.get(item) else {
+                return Vec::new();
+            };
+            let mut items = Vec::new();
+            for (index, _) in supply
+                .recipes
+                .iter()
+                .filter(|(index, _)| useful[*index] > 0.)
+            {
+                let recipe = &self.recipes[*index].recipe;
+                items.extend(recipe.get_ingredients().iter().map(|(item, _)| item));
+                if recipe.get_fuel().is_some() {
+                    items.extend(self.fuels.keys());
+                }
+            }
+            items.extend(supply.members.iter().map(|(member, _)| member));
+            items
+        };
+
+        // Rank of each item in the order they are followed: the reverse of a depth-first post-order, so that an item
+        // comes after everything that draws on it, except along a loop.
+        let mut order = Vec::new();
+        let mut visited = HashSet::new();
+        for start in outputs.iter().copied().chain(self.fuels.keys()) {
+            if !visited.insert(start) {
+                continue;
+            }
+            let mut stack = vec![(start, draws_on(start))];
+            while let Some((item, next)) = stack.last_mut() {
+                let item = *item;
+                match next.pop() {
+                    Some(other) => {
+                        if visited.insert(other) {
+                            stack.push((other, draws_on(other)));
+                        }
+                    }
+                    None => {
+                        order.push(item);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        let rank: HashMap<&Item, usize> = order
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(rank, item)| (*item, rank))
+            .collect();
+        let key = |item| (rank.get(item).copied().unwrap_or(usize::MAX), item);
+
+        // Smelting operations provided by the fuels.
+        let smelts: f64 = self
+            .fuels
+            .iter()
+            .map(|(item, smelts)| smelts * plan.burnt.get(item).copied().unwrap_or_default())
+            .sum();
+
+        let mut shares = BTreeMap::new();
+        for output in outputs.iter() {
+            let (prod, uses) = stats(output);
+            let net = prod - uses;
+            if net <= 0. || shares.contains_key(*output) {
+                continue;
+            }
+            let threshold = 1e-9 * (1. + net);
+
+            let mut share = Share::default();
+            let mut demands: BTreeMap<(usize, &Item), f64> = BTreeMap::from([(key(*output), net)]);
+            let mut smelts_needed = 0.;
+            let mut steps = 0;
+            while steps < MAX_STEPS {
+                let Some(((_, item), demand)) = demands.pop_first() else {
+                    // Fuels are only asked for once everything else is done.
+                    if smelts_needed <= threshold || smelts <= 0. {
+                        break;
+                    }
+                    for (fuel, _) in self.fuels.iter() {
+                        let burnt = plan.burnt.get(fuel).copied().unwrap_or_default();
+                        if burnt > 0. {
+                            *demands.entry(key(fuel)).or_default() +=
+                                smelts_needed * burnt / smelts;
+                        }
+                    }
+                    smelts_needed = 0.;
+                    continue;
+                };
+                steps += 1;
+                let Some(supply) = supplies.get(item).filter(|supply| supply.total > 0.) else {
+                    continue;
+                };
+                if demand <= threshold {
+                    continue;
+                }
+                let part = demand / supply.total;
+
+                for (index, amount) in supply.recipes.iter() {
+                    if useful[*index] <= 0. {
+                        continue;
+                    }
+                    let recipe = &self.recipes[*index];
+                    let crafts = part * amount / useful[*index];
+                    for (ingredient, qty) in recipe.recipe.get_ingredients() {
+                        *demands.entry(key(ingredient)).or_default() +=
+                            crafts * qty * recipe.ingredients_mult;
+                    }
+                    if let Some(fuel) = recipe.recipe.get_fuel() {
+                        smelts_needed += crafts * fuel;
+                    }
+                    share.add_costs(
+                        dimensions.iter().map(|dimension| {
+                            let cost = match dimension {
+                                None => recipe.recipe.get_cost(),
+                                Some(id) => recipe.recipe.get_dimension_costs().get(id).copied(),
+                            };
+                            (*dimension, cost.unwrap_or_default())
+                        }),
+                        crafts,
+                    );
+                }
+                for (member, amount) in supply.members.iter() {
+                    *demands.entry(key(member)).or_default() += part * amount;
+                }
+                if supply.bought > 0. {
+                    let bought = part * supply.bought;
+                    *share.bought.entry(item.clone()).or_default() += bought;
+                    if let Some(price) = self.prices.get(item) {
+                        share.add_costs(dimensions.iter().map(|d| (*d, price.get(*d))), bought);
+                    }
+                }
+                if supply.raw > 0. {
+                    let raw = part * supply.raw;
+                    *share.raws.entry(item.clone()).or_default() += raw;
+                    if let Some(ItemRole::Raw { costs, .. }) = self.items.get(item) {
+                        share.add_costs(dimensions.iter().map(|d| (*d, costs.get(*d))), raw);
+                    }
+                }
+            }
+            share.incomplete = steps >= MAX_STEPS
+                && (demands.values().any(|demand| *demand > threshold)
+                    || smelts_needed > threshold);
+            shares.insert((*output).clone(), share);
+        }
+        shares
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        optimizer::ModelRecipe,
+        recipes::{Count, Recipe},
+    };
+
+    fn item(name: &str) -> Item {
+        Item::new(name)
+    }
+
+    /// A recipe that makes one of each product from one of each ingredient.
+    fn recipe(ingredients: &[&str], products: &[&str]) -> ModelRecipe {
+        ModelRecipe {
+            recipe: Recipe::new(
+                ingredients.iter().map(|name| (item(name), 1.)).collect(),
+                products
+                    .iter()
+                    .map(|name| (item(name), Count::Fixed(1.), Default::default()))
+                    .collect(),
+            ),
+            products_mult: 1.,
+            ingredients_mult: 1.,
+            min_uses: 0.,
+            max_uses: None,
+        }
+    }
+
+    fn raw(cost: f64) -> ItemRole {
+        ItemRole::Raw {
+            costs: Costs {
+                main: cost,
+                ..Default::default()
+            },
+            available: None,
+        }
+    }
+
+    fn target(amount: f64) -> ItemRole {
+        ItemRole::Target {
+            amount,
+            priority: 0,
+        }
+    }
+
+    fn solve_shares(model: &Model) -> BTreeMap<Item, Share> {
+        let plan = model
+            .solve(&Costs {
+                main: 1.,
+                ..Default::default()
+            })
+            .unwrap();
+        model.shares(&plan)
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{actual} is not {expected}"
+        );
+    }
+
+    #[test]
+    fn byproducts() {
+        let mut model = Model {
+            recipes: vec![recipe(&["ore"], &["ingot", "slag"])],
+            items: BTreeMap::from([
+                (item("ore"), raw(1.)),
+                (item("ingot"), target(4.)),
+                (item("slag"), ItemRole::Intermediate),
+            ]),
+            ..Default::default()
+        };
+        // The surplus slag is not charged to anything.
+        let shares = solve_shares(&model);
+        assert_close(shares[&item("ingot")].raws[&item("ore")], 4.);
+        assert_close(shares[&item("ingot")].costs.main, 4.);
+
+        model.items.insert(item("slag"), target(4.));
+        let shares = solve_shares(&model);
+        assert_close(shares[&item("ingot")].raws[&item("ore")], 2.);
+        assert_close(shares[&item("slag")].raws[&item("ore")], 2.);
+        assert!(!shares[&item("ingot")].incomplete);
+    }
+
+    #[test]
+    fn tag_members() {
+        let model = Model {
+            recipes: vec![recipe(&["planks"], &["stick"])],
+            items: BTreeMap::from([
+                (item("planks"), ItemRole::Intermediate),
+                (item("oak planks"), raw(3.)),
+                (item("birch planks"), raw(1.)),
+                (item("stick"), target(2.)),
+            ]),
+            tags: BTreeMap::from([(
+                item("planks"),
+                vec![item("oak planks"), item("birch planks")],
+            )]),
+            ..Default::default()
+        };
+        let share = &solve_shares(&model)[&item("stick")];
+        assert_close(share.raws[&item("birch planks")], 2.);
+        assert!(!share.raws.contains_key(&item("oak planks")));
+        assert_close(share.costs.main, 2.);
+    }
+
+    #[test]
+    fn purchases() {
+        let model = Model {
+            recipes: vec![recipe(&["ore"], &["ingot"])],
+            items: BTreeMap::from([(item("ore"), raw(5.)), (item("ingot"), target(3.))]),
+            prices: BTreeMap::from([(
+                item("ingot"),
+                Costs {
+                    main: 2.,
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        let share = &solve_shares(&model)[&item("ingot")];
+        assert_close(share.bought[&item("ingot")], 3.);
+        assert!(share.raws.is_empty());
+        assert_close(share.costs.main, 6.);
+    }
+
+    #[test]
+    fn fuel() {
+        let mut smelting = recipe(&["ore"], &["ingot"]);
+        smelting.recipe = smelting.recipe.with_fuel(Some(1.));
+        let model = Model {
+            recipes: vec![smelting, recipe(&["ore"], &["plate"])],
+            items: BTreeMap::from([
+                (item("ore"), raw(1.)),
+                (item("ingot"), target(8.)),
+                (item("plate"), target(2.)),
+                (item("coal"), raw(4.)),
+            ]),
+            fuels: BTreeMap::from([(item("coal"), 8.)]),
+            ..Default::default()
+        };
+        let shares = solve_shares(&model);
+        assert_close(shares[&item("ingot")].raws[&item("ore")], 8.);
+        assert_close(shares[&item("ingot")].raws[&item("coal")], 1.);
+        assert_close(shares[&item("ingot")].costs.main, 12.);
+        assert_close(shares[&item("plate")].raws[&item("ore")], 2.);
+        assert!(!shares[&item("plate")].raws.contains_key(&item("coal")));
+    }
+
+    #[test]
+    fn incomplete_loop() {
+        let mut model = Model {
+            recipes: vec![
+                recipe(&["a"], &["b"]),
+                recipe(&["b"], &["a"]),
+                recipe(&["ore"], &["a"]),
+            ],
+            items: BTreeMap::from([
+                (item("ore"), raw(1.)),
+                (item("a"), ItemRole::Intermediate),
+                (item("b"), target(1.)),
+            ]),
+            ..Default::default()
+        };
+        let share = &solve_shares(&model)[&item("b")];
+        assert!(!share.incomplete);
+        assert_close(share.raws[&item("ore")], 1.);
+
+        // Nearly all of the demand goes back around the loop, so it does not fade out in time.
+        model.recipes[0].min_uses = 1e6;
+        model.recipes[1].min_uses = 1e6;
+        let share = &solve_shares(&model)[&item("b")];
+        assert!(share.incomplete);
+    }
+}
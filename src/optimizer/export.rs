@@ -0,0 +1,394 @@
+//! Export of the linear program of a [`Model`], to look at it or to solve it with other solvers.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+};
+
+use good_lp::{Expression, IntoAffineExpression, Variable};
+
+use super::{Costs, Goal, Model, Objective, SolveError, rule::Relation};
+
+/// Longest line written in the LP format before the terms go on the next line.
+const LINE_LENGTH: usize = 200;
+
+/// File format of an exported linear program.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ModelFormat {
+    /// CPLEX LP format, written like equations.
+    #[default]
+    Lp,
+    /// Free MPS format, a table of the coefficients.
+    Mps,
+}
+
+impl ModelFormat {
+    /// Usual extension of the files of this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ModelFormat::Lp => "lp",
+            ModelFormat::Mps => "mps",
+        }
+    }
+}
+
+impl Model {
+    /// Writes the linear program that [`solve`](Self::solve) runs, in the given format.
+    ///
+    /// The variables are named after the recipes, fuels, tags and purchases, and the constraints after the items.
+    /// When maximizing the output, this is the program of the highest priority level. Each next level keeps the output
+    /// of the previous ones and is solved again.
+    pub fn export(&self, weights: &Costs, format: ModelFormat) -> Result<String, SolveError> {
+        let objective = match self.goal {
+            Goal::MinimizeCost => Objective::Cost(weights),
+            Goal::MaximizeOutput => {
+                Objective::Output(self.priorities().first().copied().unwrap_or_default())
+            }
+        };
+        let mut program = self.program()?;
+        let (maximise, objective) = self.objective(&mut program, objective, &[], &[]);
+        let objective_name = if maximise { "output" } else { "cost" };
+        let note = (self.goal == Goal::MaximizeOutput && self.priorities().len() > 1).then_some(
+            "Highest priority level only. The next levels keep its output and are solved after it.",
+        );
+
+        // Names must be unique, and only use the characters every solver reads.
+        let mut used = HashSet::from([objective_name.to_string()]);
+        let columns: Vec<String> = program
+            .variables
+            .columns
+            .iter()
+            .map(|(_, column)| unique_name(&column.name, &mut used))
+            .collect();
+        let mut used = HashSet::from([objective_name.to_string()]);
+        let rows: Vec<String> = program
+            .constraints
+            .iter()
+            .map(|row| unique_name(&row.name, &mut used))
+            .collect();
+
+        let positions: HashMap<Variable, usize> = program
+            .variables
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(position, (variable, _))| (*variable, position))
+            .collect();
+        let terms = |expression: &Expression| {
+            let mut terms: Vec<(usize, f64)> = expression
+                .linear_coefficients()
+                .filter(|(_, coef)| *coef != 0.)
+                .filter_map(|(variable, coef)| Some((*positions.get(&variable)?, coef)))
+                .collect();
+            terms.sort_by_key(|(position, _)| *position);
+            terms
+        };
+
+        let mut out = String::new();
+        match format {
+            ModelFormat::Lp => {
+                let write_terms = |out: &mut String, terms: Vec<(usize, f64)>| {
+                    let mut line = out.len() - out.rfind('\n').map_or(0, |i| i + 1);
+                    if terms.is_empty() && !columns.is_empty() {
+                        let _ = write!(out, " 0 {}", columns[0]);
+                    }
+                    for (index, (position, coef)) in terms.into_iter().enumerate() {
+                        let sign = if coef < 0. {
+                            " -"
+                        } else if index > 0 {
+                            " +"
+                        } else {
+                            ""
+                        };
+                        let term = format!("{sign} {} {}", coef.abs(), columns[position]);
+                        if line + term.len() > LINE_LENGTH {
+                            out.push_str("\n ");
+                            line = 1;
+                        }
+                        line += term.len();
+                        out.push_str(&term);
+                    }
+                };
+
+                let _ = writeln!(out, "\\ Crafting tree");
+                if let Some(note) = note {
+                    let _ = writeln!(out, "\\ {note}");
+                }
+                let _ = writeln!(out, "{}", if maximise { "Maximize" } else { "Minimize" });
+                let _ = write!(out, " {objective_name}:");
+                write_terms(&mut out, terms(&objective));
+                let _ = writeln!(out, "\nSubject To");
+                for (row, name) in program.constraints.iter().zip(rows.iter()) {
+                    let _ = write!(out, " {name}:");
+                    write_terms(&mut out, terms(&row.expression));
+                    let relation = match row.relation {
+                        Relation::LessOrEqual => "<=",
+                        Relation::GreaterOrEqual => ">=",
+                        Relation::Equal => "=",
+                    };
+                    let _ = writeln!(out, " {relation} {}", row.constant);
+                }
+                let _ = writeln!(out, "Bounds");
+                for ((_, column), name) in program.variables.columns.iter().zip(columns.iter()) {
+                    if column.binary {
+                        continue;
+                    }
+                    let _ = match column.max {
+                        Some(max) if max == column.min => writeln!(out, " {name} = {max}"),
+                        Some(max) => writeln!(out, " {} <= {name} <= {max}", column.min),
+                        None => writeln!(out, " {name} >= {}", column.min),
+                    };
+                }
+                if program
+                    .variables
+                    .columns
+                    .iter()
+                    .any(|(_, column)| column.binary)
+                {
+                    let _ = writeln!(out, "Binary");
+                    for ((_, column), name) in program.variables.columns.iter().zip(columns.iter())
+                    {
+                        if column.binary {
+                            let _ = writeln!(out, " {name}");
+                        }
+                    }
+                }
+                let _ = writeln!(out, "End");
+            }
+            ModelFormat::Mps => {
+                // Coefficients of each variable, objective first.
+                let mut coefficients: Vec<Vec<(&str, f64)>> = vec![Vec::new(); columns.len()];
+                for (position, coef) in terms(&objective) {
+                    coefficients[position].push((objective_name, coef));
+                }
+                for (row, name) in program.constraints.iter().zip(rows.iter()) {
+                    for (position, coef) in terms(&row.expression) {
+                        coefficients[position].push((name, coef));
+                    }
+                }
+
+                if let Some(note) = note {
+                    let _ = writeln!(out, "* {note}");
+                }
+                let _ = writeln!(out, "NAME CraftingTree");
+                let _ = writeln!(out, "OBJSENSE");
+                let _ = writeln!(out, "    {}", if maximise { "MAX" } else { "MIN" });
+                let _ = writeln!(out, "ROWS");
+                let _ = writeln!(out, " N {objective_name}");
+                for (row, name) in program.constraints.iter().zip(rows.iter()) {
+                    let kind = match row.relation {
+                        Relation::LessOrEqual => "L",
+                        Relation::GreaterOrEqual => "G",
+                        Relation::Equal => "E",
+                    };
+                    let _ = writeln!(out, " {kind} {name}");
+                }
+                let _ = writeln!(out, "COLUMNS");
+                let mut integer = false;
+                for (((_, column), name), coefficients) in program
+                    .variables
+                    .columns
+                    .iter()
+                    .zip(columns.iter())
+                    .zip(coefficients)
+                {
+                    if column.binary != integer {
+                        integer = column.binary;
+                        let marker = if integer { "INTORG" } else { "INTEND" };
+                        let _ = writeln!(out, "    MARKER 'MARKER' '{marker}'");
+                    }
+                    if coefficients.is_empty() {
+                        let _ = writeln!(out, "    {name} {objective_name} 0");
+                    }
+                    for (row, coef) in coefficients {
+                        let _ = writeln!(out, "    {name} {row} {coef}");
+                    }
+                }
+                if integer {
+                    let _ = writeln!(out, "    MARKER 'MARKER' 'INTEND'");
+                }
+                let _ = writeln!(out, "RHS");
+                for (row, name) in program.constraints.iter().zip(rows.iter()) {
+                    if row.constant != 0. {
+                        let _ = writeln!(out, "    RHS {name} {}", row.constant);
+                    }
+                }
+                let _ = writeln!(out, "BOUNDS");
+                for ((_, column), name) in program.variables.columns.iter().zip(columns.iter()) {
+                    if column.binary {
+                        let _ = writeln!(out, " BV BND {name}");
+                        continue;
+                    }
+                    match column.max {
+                        Some(max) if max == column.min => {
+                            let _ = writeln!(out, " FX BND {name} {max}");
+                        }
+                        max => {
+                            if column.min != 0. {
+                                let _ = writeln!(out, " LO BND {name} {}", column.min);
+                            }
+                            if let Some(max) = max {
+                                let _ = writeln!(out, " UP BND {name} {max}");
+                            }
+                        }
+                    }
+                }
+                let _ = writeln!(out, "ENDATA");
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Turns a name into one made of ASCII letters, digits and `_`, that does not start with a digit
+/// and is not in `used` yet. Adds it to `used`.
+fn unique_name(name: &str, used: &mut HashSet<String>) -> String {
+    let mut base: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .take(LINE_LENGTH / 2)
+        .collect();
+    if base.is_empty() || base.starts_with(|c: char| c.is_ascii_digit()) {
+        base.insert(0, '_');
+    }
+
+    let mut name = base.clone();
+    let mut count = 1;
+    while used.contains(&name) {
+        count += 1;
+        name = format!("{base}_{count}");
+    }
+    used.insert(name.clone());
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::{
+        optimizer::{ItemRole, ModelRecipe},
+        recipes::{Count, Recipe},
+        ui::Item,
+    };
+
+    /// Smelts ore into ingots, to make 2 ingots.
+    fn model() -> Model {
+        let recipe = Recipe::new(
+            vec![(Item::new("ore"), 1.)],
+            vec![(Item::new("ingot"), Count::Fixed(1.), Default::default())],
+        )
+        .with_cost(Some(0.5));
+        Model {
+            recipes: vec![ModelRecipe {
+                recipe,
+                products_mult: 1.,
+                ingredients_mult: 1.,
+                min_uses: 0.,
+                max_uses: Some(10.),
+            }],
+            items: BTreeMap::from([
+                (
+                    Item::new("ore"),
+                    ItemRole::Raw {
+                        costs: Costs {
+                            main: 1.,
+                            ..Default::default()
+                        },
+                        available: None,
+                    },
+                ),
+                (
+                    Item::new("ingot"),
+                    ItemRole::Target {
+                        amount: 2.,
+                        priority: 0,
+                    },
+                ),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    fn weights() -> Costs {
+        Costs {
+            main: 1.,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lp() {
+        let lp = model().export(&weights(), ModelFormat::Lp).unwrap();
+        assert_eq!(
+            lp,
+            "\\ Crafting tree
+Minimize
+ cost: 0.5 recipe_0_ingot_from_ore + 1 ore_taken_from_raw
+Subject To
+ ingot: 1 recipe_0_ingot_from_ore >= 2
+ ore: - 1 recipe_0_ingot_from_ore + 1 ore_taken_from_raw >= 0
+Bounds
+ 0 <= recipe_0_ingot_from_ore <= 10
+ ore_taken_from_raw >= 0
+End
+"
+        );
+    }
+
+    #[test]
+    fn mps() {
+        let mps = model().export(&weights(), ModelFormat::Mps).unwrap();
+        assert_eq!(
+            mps,
+            "NAME CraftingTree
+OBJSENSE
+    MIN
+ROWS
+ N cost
+ G ingot
+ G ore
+COLUMNS
+    recipe_0_ingot_from_ore cost 0.5
+    recipe_0_ingot_from_ore ingot 1
+    recipe_0_ingot_from_ore ore -1
+    ore_taken_from_raw cost 1
+    ore_taken_from_raw ore 1
+RHS
+    RHS ingot 2
+BOUNDS
+ UP BND recipe_0_ingot_from_ore 10
+ENDATA
+"
+        );
+    }
+
+    #[test]
+    fn binaries() {
+        let mut model = model();
+        model.max_recipes = Some(1);
+        let lp = model.export(&weights(), ModelFormat::Lp).unwrap();
+        assert!(
+            lp.contains("\n recipe_0_used: 1 recipe_0_ingot_from_ore - 10 recipe_0_active <= 0\n")
+        );
+        assert!(lp.contains("\n max_recipes: 1 recipe_0_active <= 1\n"));
+        assert!(lp.ends_with("Binary\n recipe_0_active\nEnd\n"));
+        let mps = model.export(&weights(), ModelFormat::Mps).unwrap();
+        assert!(
+            mps.contains("    MARKER 'MARKER' 'INTORG'\n    recipe_0_active recipe_0_used -10\n")
+        );
+        assert!(mps.contains(" BV BND recipe_0_active\n"));
+    }
+
+    #[test]
+    fn names() {
+        let mut used = HashSet::from(["cost".to_string()]);
+        assert_eq!(unique_name("iron ingot", &mut used), "iron_ingot");
+        assert_eq!(unique_name("iron-ingot", &mut used), "iron_ingot_2");
+        assert_eq!(unique_name("cost", &mut used), "cost_2");
+        assert_eq!(unique_name("2 gears", &mut used), "_2_gears");
+        assert_eq!(unique_name("", &mut used), "_");
+    }
+}
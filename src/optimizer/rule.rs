@@ -0,0 +1,442 @@
+//! Everything to do with [`Rule`]s, the linear constraints written by the user.
+
+use std::{fmt::Display, iter::Peekable, num::ParseFloatError, str::Chars, str::FromStr};
+
+use crate::ui::Item;
+
+/// A linear constraint written by the user, such as `produced(glass) ≥ 2 × produced(glass pane)`.
+///
+/// Each side is a sum of terms. A term is a number, a [`Reference`], or a number times a reference.
+/// The sides are compared with `≤` (`<=`), `≥` (`>=`) or `=`.
+///
+/// Numbers may have an exponent, such as `1.5e3`, and a factor may have a sign, such as `2 × -uses(smelting)`. The
+/// name of a reference may hold parentheses, as long as each one that opens is closed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    text: String,
+    terms: Vec<(f64, Reference)>, // Left side minus right side
+    relation: Relation,
+    constant: f64, // Right side minus left side
+}
+
+impl Rule {
+    /// Retrieves the terms of the rule, with all the references moved to the left side.
+    pub fn get_terms(&self) -> &Vec<(f64, Reference)> {
+        &self.terms
+    }
+
+    /// Retrieves how the left side compares to the constant.
+    pub fn get_relation(&self) -> Relation {
+        self.relation
+    }
+
+    /// Retrieves the constant of the rule, with all the numbers moved to the right side.
+    pub fn get_constant(&self) -> f64 {
+        self.constant
+    }
+}
+
+/// A quantity of the plan a [`Rule`] can refer to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reference {
+    /// `uses(label)`: crafts of the recipes with this label.
+    Uses(String),
+    /// `produced(item)`: amount of the item produced.
+    Produced(Item),
+    /// `used(item)`: amount of the item used.
+    Used(Item),
+}
+
+impl Display for Reference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reference::Uses(label) => write!(f, "uses({label})"),
+            Reference::Produced(item) => write!(f, "produced({})", item.get_name()),
+            Reference::Used(item) => write!(f, "used({})", item.get_name()),
+        }
+    }
+}
+
+/// How the two sides of a [`Rule`] compare.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Relation {
+    /// Left side at most the right side.
+    LessOrEqual,
+    /// Left side at least the right side.
+    GreaterOrEqual,
+    /// Both sides equal.
+    Equal,
+}
+
+/// Parse error of a [`Rule`].
+#[derive(Debug, Clone)]
+pub enum ParseRuleError {
+    /// No `≤`, `≥` or `=`.
+    NoRelation,
+    /// More than one `≤`, `≥` or `=`.
+    SeveralRelations,
+    /// A side or a term is missing.
+    Missing,
+    /// A character that makes no sense here.
+    Unexpected(char),
+    /// Could not parse a number.
+    Number(ParseFloatError),
+    /// A function other than `uses`, `produced` and `used`.
+    UnknownFunction(String),
+    /// The argument of a function has no closing parenthesis.
+    Unclosed,
+    /// The argument of a function is empty.
+    EmptyName,
+    /// Two references are multiplied.
+    Nonlinear,
+}
+
+impl Display for ParseRuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseRuleError::NoRelation => write!(f, "Missing ≤, ≥ or =."),
+            ParseRuleError::SeveralRelations => write!(f, "Only one ≤, ≥ or = is allowed."),
+            ParseRuleError::Missing => write!(f, "Missing a term."),
+            ParseRuleError::Unexpected(c) => write!(f, "Unexpected '{c}'."),
+            ParseRuleError::Number(err) => err.fmt(f),
+            ParseRuleError::UnknownFunction(name) => write!(
+                f,
+                "Unknown function '{name}'. Use uses(label), produced(item) or used(item)."
+            ),
+            ParseRuleError::Unclosed => write!(f, "Missing ')'."),
+            ParseRuleError::EmptyName => write!(f, "Missing a name between the parentheses."),
+            ParseRuleError::Nonlinear => write!(f, "Quantities can only be multiplied by numbers."),
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = ParseRuleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let operators = [
+            ("<=", Relation::LessOrEqual),
+            ("≤", Relation::LessOrEqual),
+            (">=", Relation::GreaterOrEqual),
+            ("≥", Relation::GreaterOrEqual),
+            ("==", Relation::Equal),
+            ("=", Relation::Equal),
+        ];
+
+        // Find the relation. Longer operators come first, so "<=" is not read as "=".
+        // Names between parentheses may hold anything, so they are skipped.
+        let mut found = None;
+        let mut rest = s;
+        let mut offset = 0;
+        let mut depth = 0usize;
+        while !rest.is_empty() {
+            let operator = operators
+                .iter()
+                .find(|(op, _)| depth == 0 && rest.starts_with(op));
+            match operator {
+                Some((op, relation)) => {
+                    if found.is_some() {
+                        return Err(ParseRuleError::SeveralRelations);
+                    }
+                    found = Some((offset, op.len(), *relation));
+                    offset += op.len();
+                    rest = &rest[op.len()..];
+                }
+                None => {
+                    match rest.chars().next() {
+                        Some('(') => depth += 1,
+                        Some(')') => depth = depth.saturating_sub(1),
+                        _ => (),
+                    }
+                    let len = rest.chars().next().map(char::len_utf8).unwrap_or(1);
+                    offset += len;
+                    rest = &rest[len..];
+                }
+            }
+        }
+        let Some((position, len, relation)) = found else {
+            return Err(ParseRuleError::NoRelation);
+        };
+
+        let (lhs_terms, lhs_constant) = parse_side(&s[..position])?;
+        let (rhs_terms, rhs_constant) = parse_side(&s[position + len..])?;
+
+        let mut terms = lhs_terms;
+        terms.extend(
+            rhs_terms
+                .into_iter()
+                .map(|(coef, reference)| (-coef, reference)),
+        );
+
+        Ok(Self {
+            text: s.trim().to_string(),
+            terms,
+            relation,
+            constant: rhs_constant - lhs_constant,
+        })
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.text.fmt(f)
+    }
+}
+
+/// Parses a sum of terms. Returns the terms with a reference, and the sum of the others.
+fn parse_side(s: &str) -> Result<(Vec<(f64, Reference)>, f64), ParseRuleError> {
+    let mut chars = s.chars().peekable();
+    let mut terms = Vec::new();
+    let mut constant = 0.;
+
+    skip_spaces(&mut chars);
+    let mut sign = match chars.peek() {
+        Some('-') => {
+            chars.next();
+            -1.
+        }
+        Some('+') => {
+            chars.next();
+            1.
+        }
+        _ => 1.,
+    };
+
+    loop {
+        let (coef, reference) = parse_term(&mut chars)?;
+        match reference {
+            Some(reference) => terms.push((sign * coef, reference)),
+            None => constant += sign * coef,
+        }
+
+        skip_spaces(&mut chars);
+        sign = match chars.next() {
+            None => break,
+            Some('+') => 1.,
+            Some('-') => -1.,
+            Some(c) => return Err(ParseRuleError::Unexpected(c)),
+        };
+    }
+
+    Ok((terms, constant))
+}
+
+/// Parses a product of numbers and at most one reference.
+fn parse_term(chars: &mut Peekable<Chars>) -> Result<(f64, Option<Reference>), ParseRuleError> {
+    let mut coef = 1.;
+    let mut reference = None;
+
+    loop {
+        skip_spaces(chars);
+        match chars.peek() {
+            Some('+' | '-') => {
+                if chars.next() == Some('-') {
+                    coef = -coef;
+                }
+                continue;
+            }
+            Some(c) if c.is_ascii_digit() || *c == '.' => {
+                coef *= parse_number(chars)
+                    .parse::<f64>()
+                    .map_err(ParseRuleError::Number)?;
+            }
+            Some(c) if c.is_alphabetic() || *c == '_' => {
+                if reference.is_some() {
+                    return Err(ParseRuleError::Nonlinear);
+                }
+                reference = Some(parse_reference(chars)?);
+            }
+            Some(c) => return Err(ParseRuleError::Unexpected(*c)),
+            None => return Err(ParseRuleError::Missing),
+        }
+
+        // A product goes on with '*', '×', '·', or a reference right after a number.
+        skip_spaces(chars);
+        match chars.peek() {
+            Some('*' | '×' | '·') => {
+                chars.next();
+            }
+            Some(c) if reference.is_none() && (c.is_alphabetic() || *c == '_') => (),
+            _ => break,
+        }
+    }
+
+    Ok((coef, reference))
+}
+
+/// Reads a number, with its exponent if any, such as `1.5e-3`.
+fn parse_number(chars: &mut Peekable<Chars>) -> String {
+    let mut number = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+        number.push(c);
+    }
+
+    // An exponent has digits, otherwise the letter is left for what follows.
+    let mut ahead = chars.clone();
+    if let Some(e @ ('e' | 'E')) = ahead.next() {
+        let sign = ahead.next_if(|c| *c == '+' || *c == '-');
+        if ahead.peek().is_some_and(char::is_ascii_digit) {
+            number.push(e);
+            number.extend(sign);
+            while let Some(c) = ahead.next_if(char::is_ascii_digit) {
+                number.push(c);
+            }
+            *chars = ahead;
+        }
+    }
+    number
+}
+
+/// Parses `function(name)`.
+fn parse_reference(chars: &mut Peekable<Chars>) -> Result<Reference, ParseRuleError> {
+    let mut function = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+        function.push(c);
+    }
+
+    skip_spaces(chars);
+    match chars.next() {
+        Some('(') => (),
+        Some(c) => return Err(ParseRuleError::Unexpected(c)),
+        None => return Err(ParseRuleError::UnknownFunction(function)),
+    }
+
+    let mut name = String::new();
+    let mut depth = 0usize;
+    loop {
+        match chars.next() {
+            Some(')') if depth == 0 => break,
+            Some(c) => {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => (),
+                }
+                name.push(c)
+            }
+            None => return Err(ParseRuleError::Unclosed),
+        }
+    }
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ParseRuleError::EmptyName);
+    }
+
+    match function.to_lowercase().as_str() {
+        "uses" => Ok(Reference::Uses(name.to_string())),
+        "produced" => Ok(Reference::Produced(Item::new(name))),
+        "used" => Ok(Reference::Used(Item::new(name))),
+        _ => Err(ParseRuleError::UnknownFunction(function)),
+    }
+}
+
+fn skip_spaces(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(s: &str) -> Rule {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn relations() {
+        let r = rule("produced(glass) >= 2 × produced(glass pane)");
+        assert_eq!(r.get_relation(), Relation::GreaterOrEqual);
+        assert_eq!(
+            r.get_terms(),
+            &vec![
+                (1., Reference::Produced(Item::new("glass"))),
+                (-2., Reference::Produced(Item::new("glass pane"))),
+            ]
+        );
+        assert_eq!(r.get_constant(), 0.);
+        assert_eq!(
+            rule("uses(smelting) ≤ 10").get_relation(),
+            Relation::LessOrEqual
+        );
+        assert_eq!(rule("used(coal) == 3").get_relation(), Relation::Equal);
+        assert_eq!(rule("used(coal) = 3").get_relation(), Relation::Equal);
+    }
+
+    #[test]
+    fn terms() {
+        let r = rule("-uses(a) + 2 uses(b) * 3 - 4 + 1.5 = used(c) - 0.5");
+        assert_eq!(
+            r.get_terms(),
+            &vec![
+                (-1., Reference::Uses("a".to_string())),
+                (6., Reference::Uses("b".to_string())),
+                (-1., Reference::Used(Item::new("c"))),
+            ]
+        );
+        assert_eq!(r.get_constant(), 2.);
+    }
+
+    #[test]
+    fn parenthesized_names() {
+        let r = rule("produced(a <= b) >= used(x = y)");
+        assert_eq!(r.get_relation(), Relation::GreaterOrEqual);
+        assert_eq!(
+            r.get_terms(),
+            &vec![
+                (1., Reference::Produced(Item::new("a <= b"))),
+                (-1., Reference::Used(Item::new("x = y"))),
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        let r = rule("2e3 uses(a) + 1.5E-1 × -uses(b) >= -1e1 * 2");
+        assert_eq!(
+            r.get_terms(),
+            &vec![
+                (2000., Reference::Uses("a".to_string())),
+                (-0.15, Reference::Uses("b".to_string()))
+            ]
+        );
+        assert_eq!(r.get_constant(), -20.);
+        assert!(matches!(
+            "2e uses(a) <= 1".parse::<Rule>(),
+            Err(ParseRuleError::Unexpected('u'))
+        ));
+    }
+
+    #[test]
+    fn nested_parentheses() {
+        let r = rule("produced(wood (oak)) = 3");
+        assert_eq!(
+            r.get_terms(),
+            &vec![(1., Reference::Produced(Item::new("wood (oak)")))]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let err = |s: &str| s.parse::<Rule>().unwrap_err();
+        assert!(matches!(err("uses(a) + 2"), ParseRuleError::NoRelation));
+        assert!(matches!(
+            err("uses(a) <= 2 <= 3"),
+            ParseRuleError::SeveralRelations
+        ));
+        assert!(matches!(err("uses(a) <= "), ParseRuleError::Missing));
+        assert!(matches!(
+            err("uses(a) <= 2 $"),
+            ParseRuleError::Unexpected('$')
+        ));
+        assert!(matches!(
+            err("made(a) <= 2"),
+            ParseRuleError::UnknownFunction(_)
+        ));
+        assert!(matches!(err("uses(a <= 2"), ParseRuleError::NoRelation));
+        assert!(matches!(err("uses( ) <= 2"), ParseRuleError::EmptyName));
+        assert!(matches!(
+            err("uses(a) * uses(b) <= 2"),
+            ParseRuleError::Nonlinear
+        ));
+    }
+}
@@ -1,50 +1,456 @@
 //! Everything about [Recipe]s.
 
-use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt::Display, num::ParseIntError, str::FromStr};
 
+use serde::{Deserialize, Serialize};
 
 /// A recipe with ingredients that produces stuff.
+///
+/// Quantities are positive floats. Files saved when they were integers still load,
+/// as integers deserialize into floats.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Recipe<T> {
-    ingredients: Vec<(T, u8)>,
-    products: Vec<(T, u8, f64)>, // Item, nb produced, proba of success.
+    ingredients: Vec<(T, f64)>,
+    products: Vec<(T, Count, Probability)>, // Item, nb produced, proba of success.
+    #[serde(default)]
+    fuel: Option<f64>, // Number of smelting operations per craft, if the recipe has a fuel slot.
+    #[serde(default)]
+    exclusive: bool, // Exactly one product line happens per craft, with its probability as weight.
+    #[serde(default)]
+    labels: Vec<String>, // Used to select the recipes a modifier applies to.
+    #[serde(default)]
+    cost: Option<f64>, // Cost of one craft (energy, XP, emeralds...), on top of the ingredients.
+    #[serde(default)]
+    dimension_costs: BTreeMap<usize, f64>, // Cost of one craft in the other cost dimensions, by dimension id.
 }
 
 impl<T> Recipe<T> {
-    /// Creates a new recipe from an (ingerdient, quantity, probability of success) and a (product, quantity) list.
-    /// 
-    /// The probability should be between 0 and 1.
-    pub fn new(ingredients: Vec<(T, u8)>, products: Vec<(T, u8, f64)>) -> Self {
+    /// Creates a new recipe from an (ingerdient, quantity) and a (product, quantity, probability of success) list.
+    pub fn new(ingredients: Vec<(T, f64)>, products: Vec<(T, Count, Probability)>) -> Self {
         Self {
             ingredients,
             products,
+            fuel: None,
+            exclusive: false,
+            labels: Vec::new(),
+            cost: None,
+            dimension_costs: BTreeMap::new(),
         }
     }
 
+    /// Sets the cost of one craft, that is added to the cost of the ingredients.
+    pub fn with_cost(mut self, cost: Option<f64>) -> Self {
+        self.cost = cost;
+        self
+    }
+
+    /// Retrieves the cost of one craft, if any.
+    pub fn get_cost(&self) -> Option<f64> {
+        self.cost
+    }
+
+    /// Sets the cost of one craft in other cost dimensions than the main one, by dimension id.
+    pub fn with_dimension_costs(mut self, dimension_costs: BTreeMap<usize, f64>) -> Self {
+        self.dimension_costs = dimension_costs;
+        self
+    }
+
+    /// Retrieves the cost of one craft in other cost dimensions than the main one, by dimension id.
+    pub fn get_dimension_costs(&self) -> &BTreeMap<usize, f64> {
+        &self.dimension_costs
+    }
+
+    /// Same as [`get_dimension_costs`](Self::get_dimension_costs) but mutable.
+    pub fn get_mut_dimension_costs(&mut self) -> &mut BTreeMap<usize, f64> {
+        &mut self.dimension_costs
+    }
+
+    /// Sets the labels of the recipe, which select the modifiers that apply to it.
+    pub fn with_labels(mut self, labels: Vec<String>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Retrieves the labels of the recipe.
+    pub fn get_labels(&self) -> &Vec<String> {
+        &self.labels
+    }
+
+    /// Makes the product lines mutually exclusive outcomes: exactly one of them happens per craft.
+    ///
+    /// The probabilities of the products are then the weights of the outcomes, and should sum to 1.
+    pub fn with_exclusive(mut self, exclusive: bool) -> Self {
+        self.exclusive = exclusive;
+        self
+    }
+
+    /// Returns true if exactly one of the product lines happens per craft.
+    pub fn is_exclusive(&self) -> bool {
+        self.exclusive
+    }
+
+    /// Gives the recipe a fuel slot, that burns fuel for the given number of smelting operations per craft.
+    ///
+    /// `None` removes the fuel slot.
+    pub fn with_fuel(mut self, fuel: Option<f64>) -> Self {
+        self.fuel = fuel;
+        self
+    }
+
+    /// Retrieves the number of smelting operations per craft, if the recipe has a fuel slot.
+    pub fn get_fuel(&self) -> Option<f64> {
+        self.fuel
+    }
+
     /// Retrieves the ingredients of the recipe.
-    pub fn get_ingredients(&self) -> &Vec<(T, u8)> {
+    pub fn get_ingredients(&self) -> &Vec<(T, f64)> {
         &self.ingredients
     }
 
     /// Retrieves the products of the recipe.
-    pub fn get_products(&self) -> &Vec<(T, u8, f64)> {
+    pub fn get_products(&self) -> &Vec<(T, Count, Probability)> {
         &self.products
     }
 
     /// Same as [`get_ingredients`](Self::get_ingredients) but mutable.
-    pub fn get_mut_ingredients(&mut self) -> &mut Vec<(T, u8)> {
+    pub fn get_mut_ingredients(&mut self) -> &mut Vec<(T, f64)> {
         &mut self.ingredients
     }
 
     /// Same as [get_products](Self::get_products) but mutable.
-    pub fn get_mut_products(&mut self) -> &mut Vec<(T, u8, f64)> {
+    pub fn get_mut_products(&mut self) -> &mut Vec<(T, Count, Probability)> {
         &mut self.products
     }
 
     /// Deconstructs the [`Recipe`] and returns two vectors:
     /// * The first contains the ingreditents (item, quantity)
-    /// * The second contains products (item, quantity, probability of success).
-    pub fn take(self) -> (Vec<(T, u8)>, Vec<(T, u8, f64)>) {
+    /// * The second contains products (item, count, probability of success).
+    #[allow(clippy::type_complexity)]
+    pub fn take(self) -> (Vec<(T, f64)>, Vec<(T, Count, Probability)>) {
         (self.ingredients, self.products)
     }
-}
\ No newline at end of file
+}
+
+/// The number of items produced by a product line of a [`Recipe`].
+///
+/// A fixed count is saved as a plain number, so files saved before count distributions still load.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Count {
+    /// Always this number of items.
+    Fixed(f64),
+    /// Uniformly distributed between a min and a max, both included.
+    Uniform(f64, f64),
+    /// Table of (count, weight). Each count happens with a probability proportional to its weight.
+    Table(Vec<(f64, f64)>),
+}
+
+impl Count {
+    /// Expected number of items produced.
+    pub fn expected(&self) -> f64 {
+        match self {
+            Count::Fixed(n) => *n,
+            Count::Uniform(min, max) => (min + max) / 2.,
+            Count::Table(table) => {
+                let total_weight: f64 = table.iter().map(|(_, weight)| weight).sum();
+                table
+                    .iter()
+                    .map(|(count, weight)| count * weight)
+                    .sum::<f64>()
+                    / total_weight
+            }
+        }
+    }
+
+    /// Minimum number of items produced.
+    pub fn min(&self) -> f64 {
+        match self {
+            Count::Fixed(n) => *n,
+            Count::Uniform(min, _) => *min,
+            Count::Table(table) => table
+                .iter()
+                .map(|(count, _)| *count)
+                .fold(f64::INFINITY, f64::min),
+        }
+    }
+
+    /// Maximum number of items produced.
+    pub fn max(&self) -> f64 {
+        match self {
+            Count::Fixed(n) => *n,
+            Count::Uniform(_, max) => *max,
+            Count::Table(table) => table
+                .iter()
+                .map(|(count, _)| *count)
+                .fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+}
+
+impl Default for Count {
+    fn default() -> Self {
+        Count::Fixed(1.)
+    }
+}
+
+impl Display for Count {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Count::Fixed(n) => n.fmt(f),
+            Count::Uniform(min, max) => write!(f, "{min}-{max}"),
+            Count::Table(table) => {
+                let entries: Vec<String> = table
+                    .iter()
+                    .map(|(count, weight)| format!("{count}:{weight}"))
+                    .collect();
+                write!(f, "{}", entries.join(", "))
+            }
+        }
+    }
+}
+
+/// A probability.
+/// It's an exact fraction in ]0, 1].
+///
+/// It is parsed from a percentage (`2.5` or `2.5%`) or from a fraction (`1/40`),
+/// and displayed as a percentage when it has a finite decimal expansion.
+///
+/// Probabilities used to be an integer percentage: these still deserialize, with a denominator of 100.
+/// So do the floats recipes used to store, converted back to the simplest fraction.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(try_from = "ProbabilitySave")]
+pub struct Probability {
+    num: u64,
+    den: u64,
+}
+
+/// Every way a [`Probability`] has been saved.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProbabilitySave {
+    Fraction(FractionSave),
+    Float(f64),
+}
+
+/// A saved fraction, or a percentage without its denominator.
+#[derive(Deserialize)]
+struct FractionSave {
+    num: u64,
+    #[serde(default = "percent_denominator")]
+    den: u64,
+}
+
+impl TryFrom<ProbabilitySave> for Probability {
+    type Error = ParseProbaError;
+
+    fn try_from(value: ProbabilitySave) -> Result<Self, Self::Error> {
+        match value {
+            ProbabilitySave::Fraction(FractionSave { num, den }) => Self::new(num, den),
+            ProbabilitySave::Float(p) => Self::from_f64(p),
+        }
+        .ok_or(ParseProbaError::Range)
+    }
+}
+
+fn percent_denominator() -> u64 {
+    100
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+fn gcd_u128(a: u128, b: u128) -> u128 {
+    if b == 0 { a } else { gcd_u128(b, a % b) }
+}
+
+impl Probability {
+    /// Creates the probability `num / den`, if it is in ]0, 1].
+    pub fn new(num: u64, den: u64) -> Option<Self> {
+        if num == 0 || num > den {
+            None
+        } else {
+            let gcd = gcd(num, den);
+            Some(Self {
+                num: num / gcd,
+                den: den / gcd,
+            })
+        }
+    }
+
+    /// Finds the simplest percentage or fraction that converts to the given float, if it is in ]0, 1].
+    ///
+    /// A probability converted to a float and back is left unchanged.
+    pub fn from_f64(p: f64) -> Option<Self> {
+        if !(p > 0. && p <= 1.) {
+            return None;
+        }
+
+        // Percentage with a few decimals.
+        for decimals in 0..=12 {
+            let den = 100 * 10u64.pow(decimals);
+            let num = (p * den as f64).round();
+            if num / den as f64 == p {
+                return Self::new(num as u64, den);
+            }
+        }
+
+        // Continued fraction expansion, until a convergent converts back to p.
+        let (mut num, mut prev_num) = (1u64, 0u64);
+        let (mut den, mut prev_den) = (0u64, 1u64);
+        let mut x = p;
+        loop {
+            let a = x.floor() as u64;
+            let Some(next_num) = a.checked_mul(num).and_then(|n| n.checked_add(prev_num)) else {
+                break;
+            };
+            let Some(next_den) = a.checked_mul(den).and_then(|d| d.checked_add(prev_den)) else {
+                break;
+            };
+            (prev_num, num) = (num, next_num);
+            (prev_den, den) = (den, next_den);
+
+            let frac = x - x.floor();
+            if num as f64 / den as f64 == p || frac == 0. {
+                break;
+            }
+            x = 1. / frac;
+        }
+
+        Self::new(num, den)
+    }
+
+    /// Returns the probability as a float.
+    pub fn get(&self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    /// Returns true if the probabilities sum exactly to 1.
+    pub fn sums_to_one<'a>(probas: impl IntoIterator<Item = &'a Probability>) -> bool {
+        let mut sum = Some((0u128, 1u128));
+        for proba in probas {
+            let (num, den) = (proba.num as u128, proba.den as u128);
+            sum = sum.and_then(|(sum_num, sum_den)| {
+                let new_num = sum_num
+                    .checked_mul(den)?
+                    .checked_add(num.checked_mul(sum_den)?)?;
+                let new_den = sum_den.checked_mul(den)?;
+                let gcd = gcd_u128(new_num, new_den);
+                Some((new_num / gcd, new_den / gcd))
+            });
+        }
+        sum.is_some_and(|(num, den)| num == den)
+    }
+}
+
+impl Default for Probability {
+    fn default() -> Self {
+        Self { num: 1, den: 1 }
+    }
+}
+
+impl Display for Probability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let gcd = gcd(self.num, self.den);
+        let (num, den) = ((self.num / gcd) as u128, (self.den / gcd) as u128);
+
+        // Percentage with as few decimals as possible, if it is exact.
+        for decimals in 0..=17 {
+            let scaled = num * 100 * 10u128.pow(decimals);
+            if scaled % den == 0 {
+                let digits = format!("{:0>width$}", scaled / den, width = decimals as usize + 1);
+                let (int, frac) = digits.split_at(digits.len() - decimals as usize);
+                return if frac.is_empty() {
+                    write!(f, "{int}%")
+                } else {
+                    write!(f, "{int}.{frac}%")
+                };
+            }
+        }
+
+        write!(f, "{num}/{den}")
+    }
+}
+
+impl FromStr for Probability {
+    type Err = ParseProbaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.split_once('/') {
+            Some((num, den)) => {
+                let num = num.trim().parse().map_err(ParseProbaError::Parse)?;
+                let den = den.trim().parse().map_err(ParseProbaError::Parse)?;
+                Self::new(num, den).ok_or(ParseProbaError::Range)
+            }
+            None => {
+                let percent = s.strip_suffix('%').unwrap_or(s).trim();
+                let (int, frac) = percent.split_once('.').unwrap_or((percent, ""));
+                let num = format!("{int}{frac}")
+                    .parse()
+                    .map_err(ParseProbaError::Parse)?;
+                let den = 10u64
+                    .checked_pow(frac.len() as u32)
+                    .and_then(|den| den.checked_mul(100))
+                    .ok_or(ParseProbaError::Precision)?;
+                Self::new(num, den).ok_or(ParseProbaError::Range)
+            }
+        }
+    }
+}
+
+/// A proba parse error.
+#[derive(Debug, Clone)]
+pub enum ParseProbaError {
+    /// Standard [`ParseIntError`].
+    Parse(ParseIntError),
+    /// Not in ]0, 100%]
+    Range,
+    /// Too many decimals to be represented exactly.
+    Precision,
+}
+
+impl Display for ParseProbaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseProbaError::Parse(parse_int_error) => parse_int_error.fmt(f),
+            ParseProbaError::Range => write!(f, "Value has to be above 0 and at most 100%"),
+            ParseProbaError::Precision => write!(f, "Too many decimals"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proba(s: &str) -> Result<String, ParseProbaError> {
+        s.parse::<Probability>().map(|proba| proba.to_string())
+    }
+
+    #[test]
+    fn parse_probability() {
+        assert_eq!(proba("50%").unwrap(), "50%");
+        assert_eq!(proba(" 12.5 % ").unwrap(), "12.5%");
+        assert_eq!(proba("100").unwrap(), "100%");
+        assert_eq!(proba("1/3").unwrap(), "1/3");
+        assert_eq!(proba("2 / 8").unwrap(), "25%");
+        assert!(matches!(proba("0%"), Err(ParseProbaError::Range)));
+        assert!(matches!(proba("150%"), Err(ParseProbaError::Range)));
+        assert!(matches!(proba("3/2"), Err(ParseProbaError::Range)));
+        assert!(matches!(proba("half"), Err(ParseProbaError::Parse(_))));
+        assert!(matches!(
+            proba("0.000000000000000001%"),
+            Err(ParseProbaError::Precision)
+        ));
+    }
+
+    #[test]
+    fn probability_floats() {
+        assert_eq!(Probability::new(1, 3).unwrap().get(), 1. / 3.);
+        assert_eq!(Probability::from_f64(1. / 3.).unwrap().to_string(), "1/3");
+        assert_eq!(Probability::from_f64(0.125).unwrap().to_string(), "12.5%");
+        assert!(Probability::from_f64(0.).is_none());
+        assert!(Probability::from_f64(1.5).is_none());
+    }
+}
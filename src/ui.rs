@@ -1,11 +1,22 @@
 //! UI elements for the app.
 
-use std::{fmt::Display, num::ParseFloatError, ops::{Deref, DerefMut}, str::FromStr};
+use std::{
+    fmt::Display,
+    num::ParseFloatError,
+    ops::{Deref, DerefMut},
+    str::FromStr,
+};
 
 use iced::{
-    Alignment, Border, Color, Element, Font, Length::{Fill, Shrink}, advanced::{self, widget::Text}, color, widget::{
-        self, Container, TextInput, container, text::{self, IntoFragment}, text_input
-    }
+    Alignment, Border, Color, Element, Font,
+    Length::{Fill, Shrink},
+    advanced::{self, widget::Text},
+    color,
+    widget::{
+        self, Container, TextInput, container,
+        text::{self, IntoFragment},
+        text_input,
+    },
 };
 use serde::{Deserialize, Serialize};
 
@@ -27,14 +38,16 @@ impl Item {
 
     /// [`Element`] that displays an item.
     pub fn displayer<Message>(&self) -> Element<'_, Message> {
-        title_text(TitleLevel::Bald, &self.name).width(Shrink).into()
+        title_text(TitleLevel::Bald, &self.name)
+            .width(Shrink)
+            .into()
     }
 
     /// [`Element`] that builds an item.
     pub fn builder<'a, Message: Clone + 'a>(
         &'a self,
         on_build: impl Fn(Self) -> Message + 'a,
-        on_submit: Option<Message>
+        on_submit: Option<Message>,
     ) -> Element<'a, Message> {
         title_text_input(TitleLevel::Bald, "Item", &self.name)
             .on_input(move |name| on_build(Self::new(name)))
@@ -74,10 +87,11 @@ where
             weight: iced::font::Weight::Bold,
             ..Default::default()
         }),
-        TitleLevel::SubSectionTitle => widget::text(format!("---- {} ----", text.into_fragment())).font(Font {
-            weight: iced::font::Weight::Bold,
-            ..Default::default()
-        }),
+        TitleLevel::SubSectionTitle => widget::text(format!("---- {} ----", text.into_fragment()))
+            .font(Font {
+                weight: iced::font::Weight::Bold,
+                ..Default::default()
+            }),
         TitleLevel::Bald => widget::text(text).font(Font {
             weight: iced::font::Weight::Bold,
             ..Default::default()
@@ -95,16 +109,20 @@ where
     Theme: text_input::Catalog + 'a,
     Renderer: advanced::text::Renderer<Font = iced::Font>,
 {
-    
     match title_level {
-        TitleLevel::SectionTitle => text_input(placeholder, value).align_x(Alignment::Center).width(Fill).font(Font {
-            weight: iced::font::Weight::Bold,
-            ..Default::default()
-        }),
-        TitleLevel::SubSectionTitle => text_input(placeholder, format!("---- {value} ----").deref()).font(Font {
-            weight: iced::font::Weight::Bold,
-            ..Default::default()
-        }),
+        TitleLevel::SectionTitle => text_input(placeholder, value)
+            .align_x(Alignment::Center)
+            .width(Fill)
+            .font(Font {
+                weight: iced::font::Weight::Bold,
+                ..Default::default()
+            }),
+        TitleLevel::SubSectionTitle => {
+            text_input(placeholder, format!("---- {value} ----").deref()).font(Font {
+                weight: iced::font::Weight::Bold,
+                ..Default::default()
+            })
+        }
         TitleLevel::Bald => text_input(placeholder, value).font(Font {
             weight: iced::font::Weight::Bold,
             ..Default::default()
@@ -116,7 +134,7 @@ where
 pub trait ThemeColor<Theme> {
     /// Converts a theme into a color.
     fn get_color(&self, theme: &Theme) -> Color;
-} 
+}
 
 impl<Theme> ThemeColor<Theme> for Color {
     fn get_color(&self, _theme: &Theme) -> Color {
@@ -130,6 +148,21 @@ impl<T: Fn(&Theme) -> Color, Theme> ThemeColor<Theme> for T {
     }
 }
 
+/// Default style of a [TextInput], with a danger border if `invalid`.
+pub fn danger_if(invalid: bool) -> impl Fn(&iced::Theme, text_input::Status) -> text_input::Style {
+    move |theme, status| {
+        let style = text_input::default(theme, status);
+        if invalid {
+            text_input::Style {
+                border: style.border.color(theme.palette().danger),
+                ..style
+            }
+        } else {
+            style
+        }
+    }
+}
+
 /// [Container] with a contour, and padding of [SPACE].
 pub fn contoured<'a, Message, Theme, Renderer>(
     content: impl Into<Element<'a, Message, Theme, Renderer>>,
@@ -155,7 +188,12 @@ where
 /// Positive float
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct TargetAmount {
-    amount: f64
+    amount: f64,
+}
+
+impl TargetAmount {
+    /// Zero, the smallest amount.
+    pub const ZERO: Self = Self { amount: 0. };
 }
 
 impl TryFrom<f64> for TargetAmount {
@@ -163,9 +201,7 @@ impl TryFrom<f64> for TargetAmount {
 
     fn try_from(value: f64) -> Result<Self, Self::Error> {
         if value >= 0. {
-            Ok(Self {
-                amount: value
-            })
+            Ok(Self { amount: value })
         } else {
             Err(ParseTargetAmountError::Negative)
         }
@@ -204,22 +240,23 @@ pub enum ParseTargetAmountError {
     /// Could not parse float.
     Parse(ParseFloatError),
     /// Parsed float was negative.
-    Negative
+    Negative,
 }
 
 impl FromStr for TargetAmount {
     type Err = ParseTargetAmountError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        s.parse::<f64>().map_err(ParseTargetAmountError::Parse)?.try_into()
-        
+        s.parse::<f64>()
+            .map_err(ParseTargetAmountError::Parse)?
+            .try_into()
     }
 }
 
 /// Float with special Display impl.
 #[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
 pub struct DisplayFloat {
-    v: f64
+    v: f64,
 }
 
 impl DisplayFloat {
@@ -274,4 +311,4 @@ impl Display for DisplayFloat {
 pub const SPACE: u16 = 10;
 
 /// Use this color to gray out some areas
-pub const GRAY: Color = color!(0x7f7f7f, 0.2);
\ No newline at end of file
+pub const GRAY: Color = color!(0x7f7f7f, 0.2);
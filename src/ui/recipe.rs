@@ -32,6 +32,8 @@ pub enum EditableAction {
     Edit,
     /// If this is a [`Builder`], perform the action.
     BuilderAction(BuilderAction),
+    /// Removes the process cost in the cost dimension with the given id, built or not.
+    RemoveDimensionCost(usize),
 }
 
 impl EditableContent {
@@ -57,6 +59,16 @@ impl EditableContent {
                 }
                 EditableContent::Built(_) => val,
             },
+            EditableAction::RemoveDimensionCost(id) => match val {
+                EditableContent::Builder(mut builder_state) => {
+                    builder_state.perform(BuilderAction::RemoveDimensionCost(id));
+                    EditableContent::Builder(builder_state)
+                }
+                EditableContent::Built(mut recipe) => {
+                    recipe.get_mut_dimension_costs().remove(&id);
+                    EditableContent::Built(recipe)
+                }
+            },
         };
 
         replace_with::replace_with_or_abort(self, performer);
@@ -109,6 +121,15 @@ impl<'a, Message> EditableWidget<'a, Message> {
         self.content = self.content.map(|widget| widget.on_edit(on_pressed));
         self
     }
+
+    /// Sets the (id, name) of the cost dimensions other than the main one, for the process cost.
+    pub fn dimensions(mut self, dimensions: Vec<(usize, &'a str)>) -> Self {
+        self.content = match self.content {
+            Ok(widget) => Ok(widget.dimensions(dimensions)),
+            Err(builder) => Err(builder.dimensions(dimensions)),
+        };
+        self
+    }
 }
 
 impl<'a, Message: Clone + 'a> From<EditableWidget<'a, Message>> for Element<'a, Message> {
@@ -132,8 +153,10 @@ pub enum EditableContentSave {
 impl From<EditableContentSave> for EditableContent {
     fn from(value: EditableContentSave) -> Self {
         match value {
-            EditableContentSave::Builder(builder_save) => EditableContent::Builder(builder_save.into()),
+            EditableContentSave::Builder(builder_save) => {
+                EditableContent::Builder(builder_save.into())
+            }
             EditableContentSave::Built(recipe) => EditableContent::Built(recipe),
         }
     }
-}
\ No newline at end of file
+}
@@ -2,33 +2,49 @@
 
 use crate::{
     recipes::Recipe,
-    ui::{Item, SPACE, TitleLevel, recipe::layout_helpers::layout, title_text},
+    ui::{
+        DisplayFloat, Item, ParseTargetAmountError, SPACE, TargetAmount, TitleLevel, danger_if,
+        recipe::layout_helpers::layout, title_text,
+    },
 };
 
 use iced::{
     Alignment, Element,
     Length::{self},
-    widget::{Button, button, column, horizontal_rule, row, text, text_input},
+    widget::{Button, button, checkbox, column, horizontal_rule, row, text, text_input},
 };
 use more_iced_aw::{
     helpers::filter_background,
     parsed_input::{self, Parsed, ParsedInput, danger_on_err},
 };
 
+use std::{collections::BTreeMap, sync::LazyLock};
+
 pub mod helpers;
 use helpers::*;
 use serde::{Deserialize, Serialize};
 
+/// Cost shown in the cost dimensions that have no value yet.
+pub static ZERO_COST: LazyLock<parsed_input::Content<TargetAmount, ParseTargetAmountError>> =
+    LazyLock::new(|| parsed_input::Content::new(TargetAmount::ZERO));
+
 /// The state of a recipe builder widget.
 #[derive(Debug, Default, Clone)]
 pub struct BuilderState {
+    #[allow(clippy::type_complexity)]
     products: Vec<(
         Item,
-        parsed_input::Content<Quantity, ParseQuantityError>,
+        parsed_input::Content<ProductQuantity, ParseProductQuantityError>,
         parsed_input::Content<Probability, ParseProbaError>,
     )>,
     ingredients: Vec<(Item, parsed_input::Content<Quantity, ParseQuantityError>)>,
+    fuel: Option<parsed_input::Content<Quantity, ParseQuantityError>>,
+    exclusive: bool,
+    labels: String, // Comma separated
+    cost: Option<parsed_input::Content<TargetAmount, ParseTargetAmountError>>,
+    dimension_costs: BTreeMap<usize, parsed_input::Content<TargetAmount, ParseTargetAmountError>>,
     empty_qty: parsed_input::Content<Quantity, ParseQuantityError>,
+    empty_count: parsed_input::Content<ProductQuantity, ParseProductQuantityError>,
     empty_proba: parsed_input::Content<Probability, ParseProbaError>,
 }
 
@@ -39,13 +55,13 @@ pub struct BuilderState {
 #[derive(Debug, Clone)]
 pub enum BuilderAction {
     /// Adds a product with given item, quantity and probability.
-    AddProduct(Item, Quantity, Probability),
+    AddProduct(Item, ProductQuantity, Probability),
     /// Adds an ingredient with given item and quantity.
     AddIngredient(Item, Quantity),
     /// Changes the item of the given production line.
     EditProdItem(usize, Item),
     /// Changes the quantity of items produced in the given production line.
-    EditProdQty(usize, Parsed<Quantity, ParseQuantityError>),
+    EditProdQty(usize, Parsed<ProductQuantity, ParseProductQuantityError>),
     /// Changes the probability of success of the given production line. Proba must be between 0 and 1.
     EditProdProba(usize, Parsed<Probability, ParseProbaError>),
     /// Changes the item of the given ingredient line.
@@ -56,6 +72,22 @@ pub enum BuilderAction {
     DelProd(usize),
     /// Deletes the given ingredient line.
     DelIngr(usize),
+    /// Adds or removes the fuel slot.
+    ToggleFuel(bool),
+    /// Changes the number of smelting operations per craft of the fuel slot.
+    EditFuel(Parsed<Quantity, ParseQuantityError>),
+    /// Makes the production lines mutually exclusive outcomes, or independent.
+    ToggleExclusive(bool),
+    /// Changes the comma separated labels of the recipe.
+    EditLabels(String),
+    /// Adds or removes the process cost.
+    ToggleCost(bool),
+    /// Changes the process cost, paid once per craft.
+    EditCost(Parsed<TargetAmount, ParseTargetAmountError>),
+    /// Changes the process cost in the cost dimension with the given id.
+    EditDimensionCost(usize, Parsed<TargetAmount, ParseTargetAmountError>),
+    /// Removes the process cost in the cost dimension with the given id.
+    RemoveDimensionCost(usize),
     /// Enter was pressed. If the [`Builder`] has a build button, this variant will not be constructed.
     Sumbit,
 }
@@ -68,6 +100,16 @@ impl BuilderState {
 
     /// Creates a [`BuilderState`] initialised with the given [`Recipe<Item>`].
     pub fn from_recipe(recipe: Recipe<Item>) -> Self {
+        let fuel = recipe.get_fuel();
+        let exclusive = recipe.is_exclusive();
+        let labels = recipe.get_labels().join(", ");
+        let cost = recipe.get_cost();
+        let dimension_costs = recipe
+            .get_dimension_costs()
+            .iter()
+            .filter_map(|(id, cost)| Some((*id, TargetAmount::try_from(*cost).ok()?)))
+            .map(|(id, cost)| (id, parsed_input::Content::new(cost)))
+            .collect();
         let (ingredients, products) = recipe.take();
 
         Self {
@@ -76,11 +118,8 @@ impl BuilderState {
                 .map(|(item, qty, prob)| {
                     (
                         item,
-                        parsed_input::Content::new(Quantity::new(qty).unwrap_or_default()),
-                        parsed_input::Content::new(
-                            Probability::new((prob * 100.0).round().clamp(1.0, 100.0) as _)
-                                .unwrap_or_default(),
-                        ),
+                        parsed_input::Content::new(ProductQuantity::new(qty).unwrap_or_default()),
+                        parsed_input::Content::new(prob),
                     )
                 })
                 .collect(),
@@ -93,7 +132,16 @@ impl BuilderState {
                     )
                 })
                 .collect(),
+            fuel: fuel
+                .map(|fuel| parsed_input::Content::new(Quantity::new(fuel).unwrap_or_default())),
+            exclusive,
+            labels,
+            cost: cost
+                .and_then(|cost| TargetAmount::try_from(cost).ok())
+                .map(parsed_input::Content::new),
+            dimension_costs,
             empty_qty: Default::default(),
+            empty_count: Default::default(),
             empty_proba: Default::default(),
         }
     }
@@ -107,9 +155,27 @@ impl BuilderState {
                 .collect(),
             self.products
                 .into_iter()
-                .map(|(item, qty, proba)| (item, **qty, **proba as f64 / 100.0))
+                .map(|(item, qty, proba)| (item, qty.into_value().take(), *proba))
+                .collect(),
+        )
+        .with_fuel(self.fuel.map(|fuel| **fuel))
+        .with_exclusive(self.exclusive)
+        .with_labels(
+            self.labels
+                .split(',')
+                .map(str::trim)
+                .filter(|label| !label.is_empty())
+                .map(str::to_string)
                 .collect(),
         )
+        .with_dimension_costs(
+            self.dimension_costs
+                .into_iter()
+                .map(|(id, cost)| (id, **cost))
+                .filter(|(_, cost)| *cost != 0.)
+                .collect(),
+        )
+        .with_cost(self.cost.map(|cost| **cost))
     }
 
     /// Performs a [`BuilderAction`]
@@ -160,6 +226,40 @@ impl BuilderState {
                     self.ingredients.remove(index);
                 }
             }
+            BuilderAction::ToggleFuel(toggle) => {
+                if toggle {
+                    self.fuel.get_or_insert_default();
+                } else {
+                    self.fuel = None
+                }
+            }
+            BuilderAction::EditFuel(fuel) => self
+                .fuel
+                .as_mut()
+                .map(|f| f.update(fuel))
+                .unwrap_or_default(),
+            BuilderAction::ToggleExclusive(exclusive) => self.exclusive = exclusive,
+            BuilderAction::EditLabels(labels) => self.labels = labels,
+            BuilderAction::ToggleCost(toggle) => {
+                if toggle {
+                    self.cost.get_or_insert_default();
+                } else {
+                    self.cost = None
+                }
+            }
+            BuilderAction::EditCost(cost) => self
+                .cost
+                .as_mut()
+                .map(|c| c.update(cost))
+                .unwrap_or_default(),
+            BuilderAction::EditDimensionCost(id, cost) => self
+                .dimension_costs
+                .entry(id)
+                .or_insert_with(|| ZERO_COST.clone())
+                .update(cost),
+            BuilderAction::RemoveDimensionCost(id) => {
+                self.dimension_costs.remove(&id);
+            }
             BuilderAction::Sumbit => {}
         }
     }
@@ -179,6 +279,15 @@ impl BuilderState {
                 .into_iter()
                 .map(|(i, q)| (i, q.into_value()))
                 .collect(),
+            fuel: self.fuel.map(|f| f.into_value()),
+            exclusive: self.exclusive,
+            labels: self.labels,
+            cost: self.cost.map(|c| c.into_value()),
+            dimension_costs: self
+                .dimension_costs
+                .into_iter()
+                .map(|(id, c)| (id, c.into_value()))
+                .collect(),
         }
     }
 }
@@ -189,6 +298,7 @@ pub struct Builder<'a, Message> {
     on_action: Box<dyn Fn(BuilderAction) -> Message + 'a>,
     on_build: Option<Message>,
     height: Length,
+    dimensions: Vec<(usize, &'a str)>,
 }
 
 impl<'a, Message> Builder<'a, Message> {
@@ -200,6 +310,7 @@ impl<'a, Message> Builder<'a, Message> {
             on_action: Box::new(on_action),
             on_build: None,
             height: Length::Shrink,
+            dimensions: Vec::new(),
         }
     }
 
@@ -208,6 +319,13 @@ impl<'a, Message> Builder<'a, Message> {
         self.on_build = Some(on_build);
         self
     }
+
+    /// Sets the (id, name) of the cost dimensions other than the main one,
+    /// in which a process cost can be entered.
+    pub fn dimensions(mut self, dimensions: Vec<(usize, &'a str)>) -> Self {
+        self.dimensions = dimensions;
+        self
+    }
 }
 
 impl<'a, Message: Clone + 'a> From<Builder<'a, Message>> for Element<'a, Message> {
@@ -240,8 +358,8 @@ impl<'a, Message: Clone + 'a> From<Builder<'a, Message>> for Element<'a, Message
         let products = recipe_column_iter(
             products_vec,
             |(item, qty, proba)| (item, (qty, proba)),
-            |(qty, proba)| (**qty, **proba),
-            (&value.state.empty_qty, &value.state.empty_proba),
+            |(qty, proba)| ((**qty).clone(), **proba),
+            (&value.state.empty_count, &value.state.empty_proba),
             &BuilderAction::DelProd,
             &BuilderAction::EditProdItem,
             &|item, (qty, proba)| BuilderAction::AddProduct(item, qty, proba),
@@ -264,17 +382,110 @@ impl<'a, Message: Clone + 'a> From<Builder<'a, Message>> for Element<'a, Message
                         })
                         .style(danger_on_err(text_input::default))
                         .on_submit(BuilderAction::Sumbit),
-                    text("%)"),
+                    text(")"),
                 ]
                 .align_y(Alignment::Center)
                 .into()
             },
         );
 
+        let fuel =
+            row![checkbox("Fuel slot", state.fuel.is_some()).on_toggle(BuilderAction::ToggleFuel)]
+                .push_maybe(state.fuel.as_ref().map(|fuel| {
+                    row![
+                        ParsedInput::new("Smelts", fuel)
+                            .on_input(BuilderAction::EditFuel)
+                            .style(danger_on_err(text_input::default))
+                            .on_submit(BuilderAction::Sumbit),
+                        text("smelt(s) per craft")
+                    ]
+                    .spacing(SPACE)
+                    .align_y(Alignment::Center)
+                }))
+                .spacing(SPACE)
+                .align_y(Alignment::Center);
+
+        // Exclusive outcomes need odds that sum to 100%. Invalid odds are already reported.
+        let odds_mismatch = state.exclusive
+            && products_vec.iter().all(|(_, _, prob)| prob.is_valid())
+            && !Probability::sums_to_one(products_vec.iter().map(|(_, _, prob)| &**prob));
+
+        let exclusive = column![
+            checkbox(
+                "Exclusive outcomes (exactly one product line per craft)",
+                state.exclusive
+            )
+            .on_toggle(BuilderAction::ToggleExclusive)
+        ]
+        .push_maybe(odds_mismatch.then(|| {
+            text!(
+                "Odds sum to {}% instead of 100%.",
+                DisplayFloat::new(
+                    products_vec
+                        .iter()
+                        .map(|(_, _, prob)| prob.get())
+                        .sum::<f64>()
+                        * 100.
+                )
+            )
+            .style(text::danger)
+        }));
+
+        // Constraints refer to a label between parentheses.
+        let labels_invalid = state.labels.contains(['(', ')']);
+        let labels = column![
+            row![
+                text("Labels"),
+                text_input("Comma separated, used by modifiers", &state.labels)
+                    .on_input(BuilderAction::EditLabels)
+                    .on_submit(BuilderAction::Sumbit)
+                    .style(danger_if(labels_invalid))
+            ]
+            .spacing(SPACE)
+            .align_y(Alignment::Center)
+        ]
+        .push_maybe(
+            labels_invalid.then(|| text("Labels cannot contain parentheses.").style(text::danger)),
+        );
+
+        let cost = row![
+            checkbox("Process cost", state.cost.is_some()).on_toggle(BuilderAction::ToggleCost)
+        ]
+        .push_maybe(state.cost.as_ref().map(|cost| {
+            ParsedInput::new("Cost", cost)
+                .on_input(BuilderAction::EditCost)
+                .style(danger_on_err(text_input::default))
+                .on_submit(BuilderAction::Sumbit)
+        }))
+        .spacing(SPACE)
+        .align_y(Alignment::Center);
+        // Dimension costs are kept with or without the main process cost.
+        let mut cost = cost;
+        for (index, (id, name)) in value.dimensions.iter().copied().enumerate() {
+            let plus = index > 0 || state.cost.is_some();
+            cost = cost
+                .push(text!("{}{name}", if plus { "+ " } else { "" }))
+                .push(
+                    ParsedInput::new(name, state.dimension_costs.get(&id).unwrap_or(&ZERO_COST))
+                        .on_input(move |parsed| BuilderAction::EditDimensionCost(id, parsed))
+                        .style(danger_on_err(text_input::default))
+                        .on_submit(BuilderAction::Sumbit),
+                );
+        }
+        let cost = cost.push_maybe(
+            (state.cost.is_some() || !value.dimensions.is_empty()).then(|| text("per craft")),
+        );
+
         let on_build2 = value.on_build.as_ref().cloned();
         let mut content = column![{
-            let elt =
-                Element::<'_, BuilderAction>::from(layout(ingredients, products, value.height));
+            let elt = Element::<'_, BuilderAction>::from(column![
+                layout(ingredients, products, value.height),
+                horizontal_rule(SPACE),
+                fuel,
+                exclusive,
+                labels,
+                cost
+            ]);
             if let Some(on_build) = on_build2 {
                 elt.map(move |action| match action {
                     BuilderAction::Sumbit => on_build.clone(),
@@ -289,7 +500,12 @@ impl<'a, Message: Clone + 'a> From<Builder<'a, Message>> for Element<'a, Message
         let has_invalid = ingredients_vec.iter().any(|(_, qty)| !qty.is_valid())
             || products_vec
                 .iter()
-                .any(|(_, qty, prob)| !qty.is_valid() || !prob.is_valid());
+                .any(|(_, qty, prob)| !qty.is_valid() || !prob.is_valid())
+            || state.fuel.as_ref().is_some_and(|fuel| !fuel.is_valid())
+            || state.cost.as_ref().is_some_and(|cost| !cost.is_valid())
+            || state.dimension_costs.values().any(|cost| !cost.is_valid())
+            || labels_invalid
+            || odds_mismatch;
 
         if let Some(on_build) = value.on_build {
             content = content.push(horizontal_rule(SPACE));
@@ -327,8 +543,18 @@ impl<'a, Message: Clone + 'a> From<Builder<'a, Message>> for Element<'a, Message
 /// A serde compatible version of [`BuilderState`].
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BuilderSave {
-    products: Vec<(Item, Quantity, Probability)>,
+    products: Vec<(Item, ProductQuantity, Probability)>,
     ingredients: Vec<(Item, Quantity)>,
+    #[serde(default)]
+    fuel: Option<Quantity>,
+    #[serde(default)]
+    exclusive: bool,
+    #[serde(default)]
+    labels: String,
+    #[serde(default)]
+    cost: Option<TargetAmount>,
+    #[serde(default)]
+    dimension_costs: BTreeMap<usize, TargetAmount>,
 }
 
 impl From<BuilderSave> for BuilderState {
@@ -350,7 +576,17 @@ impl From<BuilderSave> for BuilderState {
                 .into_iter()
                 .map(|(i, q)| (i, parsed_input::Content::new(q)))
                 .collect(),
+            fuel: value.fuel.map(parsed_input::Content::new),
+            exclusive: value.exclusive,
+            labels: value.labels,
+            cost: value.cost.map(parsed_input::Content::new),
+            dimension_costs: value
+                .dimension_costs
+                .into_iter()
+                .map(|(id, c)| (id, parsed_input::Content::new(c)))
+                .collect(),
             empty_qty: Default::default(),
+            empty_count: Default::default(),
             empty_proba: Default::default(),
         }
     }
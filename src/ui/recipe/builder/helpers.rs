@@ -1,6 +1,6 @@
 //! Helpers for the [`Builder`](super::Builder).
 
-use std::{fmt::Display, iter, num::ParseIntError, ops::Deref, str::FromStr, sync::LazyLock};
+use std::{fmt::Display, iter, num::ParseFloatError, ops::Deref, str::FromStr, sync::LazyLock};
 
 use iced::{
     Alignment, Element,
@@ -9,12 +9,18 @@ use iced::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::ui::{recipe::BuilderAction, Item, SPACE};
+pub use crate::recipes::{ParseProbaError, Probability};
+use crate::{
+    recipes::Count,
+    ui::{Item, SPACE, recipe::BuilderAction},
+};
 
-static EMPTY_ITEM: LazyLock<Item> = LazyLock::new(|| Item::new(""));
+/// An item with no name, shown in the empty line of an item list.
+pub static EMPTY_ITEM: LazyLock<Item> = LazyLock::new(|| Item::new(""));
 
-pub(crate) fn recipe_column_iter<'a, T, U: Copy, V: Copy + 'a>(
-    vec: &'a Vec<T>,
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn recipe_column_iter<'a, T, U: Copy, V: Clone + 'a>(
+    vec: &'a [T],
     item_sep: impl Fn(&'a T) -> (&'a Item, U) + 'a,
     parsed_deref: impl Fn(U) -> V,
     empty_content: U,
@@ -30,15 +36,18 @@ pub(crate) fn recipe_column_iter<'a, T, U: Copy, V: Copy + 'a>(
         .map(move |(index, (last, (item, x)))| {
             let y = parsed_deref(x);
             row![
-                item.builder(move |i| {
-                    if i.get_name().is_empty() {
-                        del_row(index)
-                    } else if !last {
-                        edit_item(index, i)
-                    } else {
-                        add_row(i, y)
-                    }
-                }, Some(BuilderAction::Sumbit)),
+                item.builder(
+                    move |i| {
+                        if i.get_name().is_empty() {
+                            del_row(index)
+                        } else if !last {
+                            edit_item(index, i)
+                        } else {
+                            add_row(i, y.clone())
+                        }
+                    },
+                    Some(BuilderAction::Sumbit)
+                ),
                 horizontal_space().width(Fixed(SPACE as _)),
                 row_builder(index, last, x)
             ]
@@ -48,27 +57,33 @@ pub(crate) fn recipe_column_iter<'a, T, U: Copy, V: Copy + 'a>(
 }
 
 /// An item quatity.
-/// It's a positive integer that won't accept 0 when parsed.
+/// It's a strictly positive, finite float.
+///
+/// Quantities used to be integers: these still deserialize.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Quantity {
-    n: u8,
+    n: f64,
 }
 
 impl Quantity {
-    /// Creates a new quantity from a number if non zero.
-    pub fn new(n: u8) -> Option<Self> {
-        if n == 0 { None } else { Some(Quantity { n }) }
+    /// Creates a new quantity from a number if strictly positive and finite.
+    pub fn new(n: f64) -> Option<Self> {
+        if n > 0. && n.is_finite() {
+            Some(Quantity { n })
+        } else {
+            None
+        }
     }
 
     /// Consumes the quantity and returns it's number.
-    pub fn take(self) -> u8 {
+    pub fn take(self) -> f64 {
         self.n
     }
 }
 
 impl Default for Quantity {
     fn default() -> Self {
-        Quantity { n: 1 }
+        Quantity { n: 1. }
     }
 }
 
@@ -83,14 +98,14 @@ impl FromStr for Quantity {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.parse() {
-            Ok(qty) => Quantity::new(qty).ok_or(ParseQuantityError::Zero),
+            Ok(qty) => Quantity::new(qty).ok_or(ParseQuantityError::NotPositive),
             Err(error) => Err(ParseQuantityError::Parse(error)),
         }
     }
 }
 
 impl Deref for Quantity {
-    type Target = u8;
+    type Target = f64;
 
     fn deref(&self) -> &Self::Target {
         &self.n
@@ -98,86 +113,179 @@ impl Deref for Quantity {
 }
 
 #[derive(Debug, Clone)]
-/// Non zero [`ParseIntError`].
+/// Strictly positive [`ParseFloatError`].
 pub enum ParseQuantityError {
-    /// Standard [`ParseIntError`]
-    Parse(ParseIntError),
-    /// Got 0
-    Zero,
+    /// Standard [`ParseFloatError`]
+    Parse(ParseFloatError),
+    /// Got 0, a negative number or infinity.
+    NotPositive,
 }
 
 impl Display for ParseQuantityError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ParseQuantityError::Parse(parse_int_error) => parse_int_error.fmt(f),
-            ParseQuantityError::Zero => write!(f, "Can't be zero."),
+            ParseQuantityError::Parse(parse_float_error) => parse_float_error.fmt(f),
+            ParseQuantityError::NotPositive => write!(f, "Has to be strictly positive."),
         }
     }
 }
 
-/// A probability.
-/// It's an integer between 0 and 100 (%).
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub struct Probability {
-    p: u8,
+/// The number of items produced by a product line.
+/// It's a [`Count`] that never produces a negative number of items, and produces some on average.
+///
+/// It is parsed from a number (`2`), a range (`1-3`) or a table of counts and weights (`0:1, 1:2, 2:1`).
+///
+/// Product quantities used to be a [`Quantity`]: these still deserialize, as a fixed count.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ProductQuantity {
+    count: Count,
 }
 
-impl Probability {
-    /// Creates a new probability, if the given int is a valid strictly positive percentage.
-    pub fn new(p: u8) -> Option<Self> {
-        if p <= 0 || p > 100 {
-            None
+impl ProductQuantity {
+    /// Creates a new product quantity, if the count is valid.
+    pub fn new(count: Count) -> Option<Self> {
+        let valid = match &count {
+            Count::Fixed(_) => true,
+            Count::Uniform(min, max) => min <= max,
+            Count::Table(table) => {
+                !table.is_empty()
+                    && table
+                        .iter()
+                        .all(|(_, weight)| *weight > 0. && weight.is_finite())
+            }
+        };
+
+        if valid && count.min() >= 0. && count.max().is_finite() && count.expected() > 0. {
+            Some(Self { count })
         } else {
-            Some(Self { p })
+            None
         }
     }
-}
 
-impl Default for Probability {
-    fn default() -> Self {
-        Self { p: 100 }
+    /// Consumes the product quantity and returns it's count.
+    pub fn take(self) -> Count {
+        self.count
     }
 }
 
-impl Display for Probability {
+impl Display for ProductQuantity {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.p.fmt(f)
+        self.count.fmt(f)
     }
 }
 
-impl FromStr for Probability {
-    type Err = ParseProbaError;
+impl FromStr for ProductQuantity {
+    type Err = ParseProductQuantityError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.parse() {
-            Ok(p) => Self::new(p).ok_or(ParseProbaError::Range),
-            Err(err) => Err(ParseProbaError::Parse(err)),
-        }
+        let parse = |s: &str| {
+            s.trim()
+                .parse::<f64>()
+                .map_err(ParseProductQuantityError::Parse)
+        };
+
+        let count = if s.contains(':') {
+            Count::Table(
+                s.split(',')
+                    .map(|entry| match entry.split_once(':') {
+                        Some((count, weight)) => Ok((parse(count)?, parse(weight)?)),
+                        None => Err(ParseProductQuantityError::Table),
+                    })
+                    .collect::<Result<_, _>>()?,
+            )
+        } else if let Some(index) = range_separator(s) {
+            Count::Uniform(parse(&s[..index])?, parse(&s[index + 1..])?)
+        } else {
+            Count::Fixed(parse(s)?)
+        };
+
+        Self::new(count).ok_or(ParseProductQuantityError::Invalid)
     }
 }
 
-impl Deref for Probability {
-    type Target = u8;
+/// Position of the `-` between the bounds of a range, skipping the sign of the first bound
+/// and the signs of exponents like in `1e-3`.
+fn range_separator(s: &str) -> Option<usize> {
+    let s_trimmed = s.trim_start();
+    let offset = s.len() - s_trimmed.len();
+    s_trimmed
+        .char_indices()
+        .skip(1)
+        .find(|&(index, c)| c == '-' && !s_trimmed[..index].ends_with(['e', 'E']))
+        .map(|(index, _)| offset + index)
+}
+
+impl Deref for ProductQuantity {
+    type Target = Count;
 
     fn deref(&self) -> &Self::Target {
-        &self.p
+        &self.count
     }
 }
 
-/// A proba parse error.
+/// A product quantity parse error.
 #[derive(Debug, Clone)]
-pub enum ParseProbaError {
-    /// Standard [`ParseIntError`].
-    Parse(ParseIntError),
-    /// Not a non zero percentage
-    Range,
+pub enum ParseProductQuantityError {
+    /// Standard [`ParseFloatError`].
+    Parse(ParseFloatError),
+    /// A table entry is not of the form `count:weight`.
+    Table,
+    /// Negative count, reversed range, non positive weight, or nothing produced on average.
+    Invalid,
 }
 
-impl Display for ParseProbaError {
+impl Display for ParseProductQuantityError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ParseProbaError::Parse(parse_int_error) => parse_int_error.fmt(f),
-            ParseProbaError::Range => write!(f, "Value has to be between 1 and 100"),
+            ParseProductQuantityError::Parse(parse_float_error) => parse_float_error.fmt(f),
+            ParseProductQuantityError::Table => {
+                write!(f, "Table entries are written count:weight.")
+            }
+            ParseProductQuantityError::Invalid => write!(
+                f,
+                "Counts can't be negative, ranges are min-max, weights are positive, and something has to be produced."
+            ),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(s: &str) -> Option<Count> {
+        s.parse::<ProductQuantity>().ok().map(ProductQuantity::take)
+    }
+
+    #[test]
+    fn fixed() {
+        assert_eq!(count("2"), Some(Count::Fixed(2.)));
+        assert_eq!(count(" 1.5 "), Some(Count::Fixed(1.5)));
+        assert_eq!(count("1e-3"), Some(Count::Fixed(1e-3)));
+        assert_eq!(count("0"), None);
+        assert_eq!(count("-1"), None);
+        assert_eq!(count("two"), None);
+    }
+
+    #[test]
+    fn range() {
+        assert_eq!(count("2-5"), Some(Count::Uniform(2., 5.)));
+        assert_eq!(count(" 1 - 3 "), Some(Count::Uniform(1., 3.)));
+        assert_eq!(count("1e-3-2E-2"), Some(Count::Uniform(1e-3, 2e-2)));
+        assert_eq!(count("0-1e2"), Some(Count::Uniform(0., 100.)));
+        assert_eq!(count("5-2"), None);
+        assert_eq!(count("-1-2"), None);
+        assert_eq!(count("0-0"), None);
+    }
+
+    #[test]
+    fn table() {
+        assert_eq!(
+            count("0:1, 1:2, 2:1"),
+            Some(Count::Table(vec![(0., 1.), (1., 2.), (2., 1.)]))
+        );
+        assert_eq!(count("1:1, 2"), None);
+        assert_eq!(count("1:0"), None);
+        assert_eq!(count("0:1"), None);
+    }
+}
@@ -7,8 +7,8 @@ use iced::{
 };
 
 use crate::{
-    recipes::Recipe,
-    ui::{Item, SPACE, TitleLevel, recipe::layout_helpers::layout, title_text},
+    recipes::{Count, Recipe},
+    ui::{DisplayFloat, Item, SPACE, TitleLevel, recipe::layout_helpers::layout, title_text},
 };
 
 /// A widget that displays a recipe
@@ -16,6 +16,7 @@ pub struct RecipeWidget<'a, Message> {
     recipe: &'a Recipe<Item>,
     on_edit: Option<Message>,
     height: Length,
+    dimensions: Vec<(usize, &'a str)>,
 }
 
 impl<'a, Message> RecipeWidget<'a, Message> {
@@ -25,9 +26,17 @@ impl<'a, Message> RecipeWidget<'a, Message> {
             recipe,
             on_edit: None,
             height: Shrink,
+            dimensions: Vec::new(),
         }
     }
 
+    /// Sets the (id, name) of the cost dimensions other than the main one,
+    /// in which the process cost is displayed.
+    pub fn dimensions(mut self, dimensions: Vec<(usize, &'a str)>) -> Self {
+        self.dimensions = dimensions;
+        self
+    }
+
     /// Adds an Edit button that sends a message.
     pub fn on_edit(mut self, on_edit: Message) -> Self {
         self.on_edit = Some(on_edit);
@@ -53,13 +62,48 @@ impl<'a, Message: Clone + 'a> From<RecipeWidget<'a, Message>> for Element<'a, Me
             .map(|(item, quantity, proba)| {
                 row![
                     item.displayer(),
-                    text!("{quantity} ({:.0}%)", proba * 100.0)
+                    text!(
+                        "{quantity}{} ({proba})",
+                        match quantity {
+                            Count::Fixed(_) => String::new(),
+                            _ => format!(", avg {}", DisplayFloat::new(quantity.expected())),
+                        }
+                    )
                 ]
                 .spacing(SPACE)
                 .into()
             });
 
-        let mut content = column![layout(ingredients, products, value.height)];
+        let mut content = column![layout(ingredients, products, value.height)]
+            .push_maybe(
+                value
+                    .recipe
+                    .get_fuel()
+                    .map(|fuel| text!("Fuel slot: {fuel} smelt(s) per craft")),
+            )
+            .push_maybe(
+                value
+                    .recipe
+                    .is_exclusive()
+                    .then(|| text("Exclusive outcomes: exactly one product line per craft")),
+            )
+            .push_maybe(
+                (!value.recipe.get_labels().is_empty())
+                    .then(|| text!("Labels: {}", value.recipe.get_labels().join(", "))),
+            )
+            .push_maybe(
+                value
+                    .recipe
+                    .get_cost()
+                    .map(|cost| DisplayFloat::new(cost).to_string())
+                    .into_iter()
+                    .chain(value.dimensions.iter().filter_map(|(id, name)| {
+                        let cost = value.recipe.get_dimension_costs().get(id)?;
+                        Some(format!("{} {name}", DisplayFloat::new(*cost)))
+                    }))
+                    .reduce(|costs, cost| format!("{costs} + {cost}"))
+                    .map(|costs| text!("Process cost: {costs} per craft")),
+            );
 
         if let Some(on_edit) = value.on_edit {
             content =
@@ -1,26 +1,29 @@
-use iced::{widget::{column, horizontal_rule, row, vertical_rule}, Element, Length};
+use iced::{
+    Element, Length,
+    widget::{column, horizontal_rule, row, vertical_rule},
+};
 
-use crate::ui::{title_text, TitleLevel, SPACE};
+use crate::ui::{SPACE, TitleLevel, title_text};
 
 /// Builds the general layout of a recipe widget
 pub fn layout<'a, Message: 'a>(
     ingredient_iter: impl Iterator<Item = Element<'a, Message>>,
     product_iter: impl Iterator<Item = Element<'a, Message>>,
-    height: Length
+    height: Length,
 ) -> Element<'a, Message> {
     let ingredient_col = column![
         title_text(TitleLevel::SectionTitle, "Ingredients"),
         horizontal_rule(SPACE),
-
         column(ingredient_iter)
     ];
 
     let products_col = column![
         title_text(TitleLevel::SectionTitle, "Products"),
         horizontal_rule(SPACE),
-
         column(product_iter)
     ];
 
-    row![ingredient_col, vertical_rule(SPACE), products_col].height(height).into()
-}
\ No newline at end of file
+    row![ingredient_col, vertical_rule(SPACE), products_col]
+        .height(height)
+        .into()
+}